@@ -0,0 +1,40 @@
+-- Schema as created by init_archive_schema before migrations were tracked.
+-- Archives from that time already have the tables, so everything here is
+-- idempotent. Their missing app_version columns are added by the code,
+-- SQLite has no ADD COLUMN IF NOT EXISTS.
+CREATE TABLE IF NOT EXISTS pv_power_archive (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    pv_production INTEGER NOT NULL,
+    supply_power INTEGER NOT NULL,
+    battery_power INTEGER NOT NULL,
+    consumption INTEGER NOT NULL,
+    battery_state TEXT NOT NULL,
+    supply_state TEXT NOT NULL,
+    battery_percent INTEGER NOT NULL,
+    battery_energy_wh INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    archived_at TEXT DEFAULT (datetime('now', 'utc')),
+    app_version TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_archive_power_timestamp ON pv_power_archive(timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_archive_power_archived_at ON pv_power_archive(archived_at DESC);
+
+CREATE TABLE IF NOT EXISTS pv_energy_archive (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    grid_buy_wh INTEGER NOT NULL,
+    grid_sell_wh INTEGER NOT NULL,
+    production_energy_wh INTEGER NOT NULL,
+    consumption_energy_wh INTEGER NOT NULL,
+    battery_loaded_wh INTEGER NOT NULL,
+    battery_discharge_wh INTEGER NOT NULL,
+    battery_cycles INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    archived_at TEXT DEFAULT (datetime('now', 'utc')),
+    app_version TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_archive_energy_timestamp ON pv_energy_archive(timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_archive_energy_archived_at ON pv_energy_archive(archived_at DESC);
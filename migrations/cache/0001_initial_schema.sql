@@ -0,0 +1,44 @@
+-- Schema as created by init_cache_schema before migrations were tracked.
+-- Caches from that time already have the tables, so everything here is
+-- idempotent. Their missing app_version columns are added by the code,
+-- SQLite has no ADD COLUMN IF NOT EXISTS.
+CREATE TABLE IF NOT EXISTS pv_power_cache (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL UNIQUE,
+    pv_production INTEGER NOT NULL,
+    supply_power INTEGER NOT NULL,
+    battery_power INTEGER NOT NULL,
+    consumption INTEGER NOT NULL,
+    battery_state TEXT NOT NULL,
+    supply_state TEXT NOT NULL,
+    battery_percent INTEGER NOT NULL CHECK (battery_percent >= 0 AND battery_percent <= 100),
+    battery_energy_wh INTEGER NOT NULL CHECK (battery_energy_wh >= 0),
+    created_at TEXT DEFAULT (datetime('now', 'utc')),
+    app_version TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_cache_power_timestamp ON pv_power_cache(timestamp DESC);
+
+CREATE TABLE IF NOT EXISTS pv_energy_cache (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL UNIQUE,
+    grid_buy_wh INTEGER NOT NULL CHECK (grid_buy_wh >= 0),
+    grid_sell_wh INTEGER NOT NULL CHECK (grid_sell_wh >= 0),
+    production_energy_wh INTEGER NOT NULL CHECK (production_energy_wh >= 0),
+    consumption_energy_wh INTEGER NOT NULL CHECK (consumption_energy_wh >= 0),
+    battery_loaded_wh INTEGER NOT NULL CHECK (battery_loaded_wh >= 0),
+    battery_discharge_wh INTEGER NOT NULL CHECK (battery_discharge_wh >= 0),
+    battery_cycles INTEGER NOT NULL CHECK (battery_cycles >= 0),
+    created_at TEXT DEFAULT (datetime('now', 'utc')),
+    app_version TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_cache_energy_timestamp ON pv_energy_cache(timestamp DESC);
+
+CREATE TABLE IF NOT EXISTS state_transitions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    from_state TEXT NOT NULL,
+    to_state TEXT NOT NULL,
+    reason TEXT NOT NULL
+);
@@ -0,0 +1,50 @@
+-- Schema as created by init_schema before migrations were tracked. Databases
+-- from that time already have the tables, so everything here is idempotent.
+CREATE TABLE IF NOT EXISTS pv_power_data (
+    id BIGSERIAL PRIMARY KEY,
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL UNIQUE,
+    pv_production INTEGER NOT NULL,
+    supply_power INTEGER NOT NULL,
+    battery_power INTEGER NOT NULL,
+    consumption INTEGER NOT NULL,
+    battery_state VARCHAR(20) NOT NULL,
+    supply_state VARCHAR(20) NOT NULL,
+    battery_percent INTEGER NOT NULL CHECK (battery_percent >= 0 AND battery_percent <= 100),
+    battery_energy_wh BIGINT NOT NULL CHECK (battery_energy_wh >= 0),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    app_version VARCHAR(32)
+);
+
+CREATE INDEX IF NOT EXISTS idx_pv_power_timestamp ON pv_power_data(timestamp DESC);
+
+CREATE TABLE IF NOT EXISTS pv_energy_data (
+    id BIGSERIAL PRIMARY KEY,
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL UNIQUE,
+    grid_buy_wh BIGINT NOT NULL CHECK (grid_buy_wh >= 0),
+    grid_sell_wh BIGINT NOT NULL CHECK (grid_sell_wh >= 0),
+    production_energy_wh BIGINT NOT NULL CHECK (production_energy_wh >= 0),
+    consumption_energy_wh BIGINT NOT NULL CHECK (consumption_energy_wh >= 0),
+    battery_loaded_wh BIGINT NOT NULL CHECK (battery_loaded_wh >= 0),
+    battery_discharge_wh BIGINT NOT NULL CHECK (battery_discharge_wh >= 0),
+    battery_cycles INTEGER NOT NULL CHECK (battery_cycles >= 0),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    app_version VARCHAR(32)
+);
+
+CREATE INDEX IF NOT EXISTS idx_pv_energy_timestamp ON pv_energy_data(timestamp DESC);
+
+CREATE TABLE IF NOT EXISTS pv_tariff_daily (
+    day DATE NOT NULL,
+    tariff_window VARCHAR(32) NOT NULL,
+    import_wh DOUBLE PRECISION NOT NULL DEFAULT 0,
+    export_wh DOUBLE PRECISION NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (day, tariff_window)
+);
+
+-- Tables created before the version column existed
+ALTER TABLE pv_power_data ADD COLUMN IF NOT EXISTS app_version VARCHAR(32);
+ALTER TABLE pv_energy_data ADD COLUMN IF NOT EXISTS app_version VARCHAR(32);
+
+-- Tables created while the battery energy was still an INTEGER
+ALTER TABLE pv_power_data ALTER COLUMN battery_energy_wh TYPE BIGINT;
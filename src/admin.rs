@@ -0,0 +1,149 @@
+use crate::db::SqliteCache;
+use crate::mqtt::IncomingMessage;
+use serde_json::json;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminCommand {
+    ClearCache { token: String },
+    ClearArchive { token: String },
+}
+
+impl AdminCommand {
+    /// Maps a message on `solar/<id>/cmd/<command>` to an admin command.
+    /// The payload carries the confirmation token.
+    pub fn parse(message: &IncomingMessage) -> Option<Self> {
+        let command = message.topic.rsplit('/').next()?;
+        let token = message.payload.trim().to_string();
+
+        match command {
+            "clear-cache" => Some(AdminCommand::ClearCache { token }),
+            "clear-archive" => Some(AdminCommand::ClearArchive { token }),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AdminCommand::ClearCache { .. } => "clear-cache",
+            AdminCommand::ClearArchive { .. } => "clear-archive",
+        }
+    }
+
+    fn token(&self) -> &str {
+        match self {
+            AdminCommand::ClearCache { token } | AdminCommand::ClearArchive { token } => token,
+        }
+    }
+}
+
+/// Runs the command if the token matches the configured one and returns the
+/// response payload. Without a configured token admin commands are disabled.
+pub async fn execute(
+    command: &AdminCommand,
+    cache: Option<&SqliteCache>,
+    expected_token: Option<&str>,
+) -> serde_json::Value {
+    let Some(expected_token) = expected_token else {
+        warn!(
+            command = command.name(),
+            "Admin command ignored, no token configured"
+        );
+        return rejected(command, "admin commands disabled");
+    };
+
+    if command.token() != expected_token {
+        warn!(
+            command = command.name(),
+            "Admin command rejected, invalid token"
+        );
+        return rejected(command, "invalid confirmation token");
+    }
+
+    let Some(cache) = cache else {
+        return rejected(command, "no cache configured");
+    };
+
+    let result = match command {
+        AdminCommand::ClearCache { .. } => cache.clear_cache().await,
+        AdminCommand::ClearArchive { .. } => cache.clear_archive().await,
+    };
+
+    match result {
+        Ok((power_removed, energy_removed)) => {
+            info!(
+                command = command.name(),
+                power_removed, energy_removed, "Admin command executed"
+            );
+            json!({
+                "command": command.name(),
+                "status": "ok",
+                "power_rows_removed": power_removed,
+                "energy_rows_removed": energy_removed,
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            })
+        }
+        Err(e) => {
+            warn!(command = command.name(), error = %e, "Admin command failed");
+            json!({
+                "command": command.name(),
+                "status": "error",
+                "error": e.to_string(),
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            })
+        }
+    }
+}
+
+fn rejected(command: &AdminCommand, reason: &str) -> serde_json::Value {
+    json!({
+        "command": command.name(),
+        "status": "rejected",
+        "reason": reason,
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    })
+}
+
+#[tokio::test]
+async fn test_clear_cache_requires_token() {
+    use crate::calculator::ProcessedData;
+    use crate::config::SqliteCacheConfig;
+
+    let config = SqliteCacheConfig {
+        max_cache_size_mb: 100,
+        cache_db_path: "data/test_admin_cache.db".to_string(),
+        archive_db_path: "data/test_admin_archive.db".to_string(),
+        sync_batch_size: 100,
+        cleanup_threshold_days: 150,
+        mirror_to_cache: false,
+    };
+    let cache = SqliteCache::new(config).await.unwrap();
+    cache
+        .store_power_data(&ProcessedData::default())
+        .await
+        .unwrap();
+
+    let message = IncomingMessage {
+        topic: "solar/pv_api/cmd/clear-cache".to_string(),
+        payload: "wrong".to_string(),
+    };
+    let command = AdminCommand::parse(&message).unwrap();
+    let response = execute(&command, Some(&cache), Some("secret")).await;
+
+    assert_eq!(response["status"], "rejected");
+    assert!(cache.get_cache_stats().await.unwrap().power_records_cached >= 1);
+
+    let message = IncomingMessage {
+        topic: "solar/pv_api/cmd/clear-cache".to_string(),
+        payload: "secret".to_string(),
+    };
+    let command = AdminCommand::parse(&message).unwrap();
+    let response = execute(&command, Some(&cache), Some("secret")).await;
+
+    assert_eq!(response["status"], "ok");
+    assert!(response["power_rows_removed"].as_u64().unwrap() >= 1);
+    assert_eq!(
+        cache.get_cache_stats().await.unwrap().power_records_cached,
+        0
+    );
+}
@@ -0,0 +1,208 @@
+use crate::calculator::{BatteryState, BatteryStatus, DataHistory, ProcessedData, SupplyState};
+use crate::config::{Config, DatabaseConfig, SqliteCacheConfig};
+use crate::db::{PostgresDatabase, SqliteCache};
+use color_eyre::{Result, eyre::eyre};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const BENCH_CACHE_PATH: &str = "data/bench_cache.db";
+
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    pub records: usize,
+    pub rows_per_sec: f64,
+    pub store_p50: Duration,
+    pub store_p99: Duration,
+    pub sync_duration: Option<Duration>,
+    pub total_duration: Duration,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} records in {:.2?} ({:.1} rows/sec), store p50 {:.2?}, p99 {:.2?}, sync {}",
+            self.records,
+            self.total_duration,
+            self.rows_per_sec,
+            self.store_p50,
+            self.store_p99,
+            match self.sync_duration {
+                Some(duration) => format!("{:.2?}", duration),
+                None => "skipped".to_string(),
+            }
+        )
+    }
+}
+
+/// Runs the benchmark with a dedicated cache file. Neither the inverter nor
+/// MQTT are touched. The synthetic rows are upserted with current timestamps,
+/// so the sync only runs against an explicit `database_url`, never the
+/// configured database.
+pub async fn run_bench(records: usize, database_url: Option<String>) -> Result<BenchReport> {
+    let config = Config::new()?;
+    let cache_config = SqliteCacheConfig {
+        cache_db_path: BENCH_CACHE_PATH.to_string(),
+        // Sync everything in one pass
+        sync_batch_size: records.max(1) as i64,
+        ..config.sqlite_cache_config.clone()
+    };
+
+    let pgdb = match database_url {
+        Some(database_url) if database_url == config.database_config.database_url => {
+            return Err(eyre!(
+                "Refusing to benchmark against the configured database, pass a scratch database via --database-url"
+            ));
+        }
+        Some(database_url) => Some(
+            PostgresDatabase::new(DatabaseConfig {
+                database_url,
+                read_database_url: None,
+                ..config.database_config.clone()
+            })
+            .await?,
+        ),
+        None => {
+            info!("No --database-url given, benchmarking cache only");
+            None
+        }
+    };
+    let cache = SqliteCache::new(cache_config).await?;
+
+    bench(records, &cache, pgdb.as_ref()).await
+}
+
+pub async fn bench(
+    records: usize,
+    cache: &SqliteCache,
+    pgdb: Option<&PostgresDatabase>,
+) -> Result<BenchReport> {
+    info!(records, "Starting synthetic benchmark");
+    cache.clear_cache().await?;
+
+    let start = Instant::now();
+    let mut latencies = Vec::with_capacity(records);
+
+    for i in 0..records {
+        let (power_data, energy_data) = synthetic_data(i);
+
+        let store_start = Instant::now();
+        cache.store_power_data(&power_data).await?;
+        cache.store_energy_data(&energy_data).await?;
+        latencies.push(store_start.elapsed());
+    }
+
+    let sync_duration = match pgdb {
+        Some(pgdb)
+            if pgdb
+                .health_check()
+                .await
+                .is_ok_and(|h| h == crate::db::PostgresHealth::Healthy) =>
+        {
+            let sync_start = Instant::now();
+            cache.sync_to_postgres(pgdb).await?;
+            Some(sync_start.elapsed())
+        }
+        Some(_) => {
+            warn!("PostgreSQL not available, benchmarking cache only");
+            None
+        }
+        None => None,
+    };
+
+    let total_duration = start.elapsed();
+    cache.clear_cache().await?;
+
+    latencies.sort();
+    // Every record is one power and one energy row
+    let rows = records * 2;
+    let report = BenchReport {
+        records,
+        rows_per_sec: rows as f64 / total_duration.as_secs_f64(),
+        store_p50: percentile(&latencies, 0.50),
+        store_p99: percentile(&latencies, 0.99),
+        sync_duration,
+        total_duration,
+    };
+
+    info!("Benchmark finished: {}", report);
+    Ok(report)
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[index]
+}
+
+fn synthetic_data(i: usize) -> (ProcessedData, DataHistory) {
+    let step = i as u64;
+    let production = (i % 5000) as u16;
+    let consumption = 500 + (i % 1500) as u16;
+
+    let supply_state = if production > consumption {
+        SupplyState::Surplus((production - consumption) as u32)
+    } else {
+        SupplyState::Demand((consumption - production) as u32)
+    };
+
+    let power_data = ProcessedData {
+        supply_state,
+        battery_status: BatteryStatus {
+            battery_state: BatteryState::Loading((i % 3000) as u32),
+            battery_percent: (i % 101) as u8,
+            battery_energy: (i % 101) as f32 * 100.0,
+            battery_temperature: None,
+        },
+        full_production: production,
+        consumption,
+        ..Default::default()
+    };
+
+    let energy_data = DataHistory {
+        grid_buy: 1_000 + step,
+        grid_sell: 2_000 + step,
+        production_energy: 10_000 + step * 3,
+        consumption_energy: 5_000 + step * 2,
+        battery_loaded: 3_000 + step,
+        battery_discharge: 1_000 + step,
+        battery_cycles: 0,
+        self_consumed_energy: 8_000 + step * 2,
+        counter_reset: false,
+    };
+
+    (power_data, energy_data)
+}
+
+#[tokio::test]
+async fn test_tiny_bench() {
+    let config = SqliteCacheConfig {
+        max_cache_size_mb: 100,
+        cache_db_path: "data/test_bench_cache.db".to_string(),
+        archive_db_path: "data/test_bench_archive.db".to_string(),
+        sync_batch_size: 20,
+        cleanup_threshold_days: 150,
+        mirror_to_cache: false,
+    };
+    let cache = SqliteCache::new(config).await.unwrap();
+    let pgdb = PostgresDatabase::new(Config::new().unwrap().database_config)
+        .await
+        .unwrap();
+
+    let report = bench(20, &cache, Some(&pgdb)).await.unwrap();
+
+    assert_eq!(report.records, 20);
+    assert!(report.rows_per_sec > 0.0);
+    assert!(report.store_p50 <= report.store_p99);
+    assert!(report.total_duration > Duration::ZERO);
+}
+
+#[tokio::test]
+async fn test_bench_refuses_configured_database() {
+    let configured = Config::new().unwrap().database_config.database_url;
+    let result = run_bench(1, Some(configured)).await;
+    assert!(result.is_err());
+}
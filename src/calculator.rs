@@ -1,18 +1,57 @@
-use crate::collector::RawPVData;
-use crate::config;
+use crate::collector::{RawEnergyData, RawPVData};
+use crate::config::{self, ConsumptionMode, ExternalMeterMode, ProductionSource};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::cmp::Ordering;
 use std::fmt;
+use std::str::FromStr;
 use tracing::warn;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProcessedData {
     pub supply_state: SupplyState,
     pub battery_status: BatteryStatus,
     pub full_production: u16,
     pub consumption: u16,
+    pub phase_power: PhasePower,
+    pub battery_limits: Option<BatteryLimits>,
+    /// Only set with `grid_charge_detection` enabled
+    pub battery_charging_from_grid: Option<bool>,
+    pub autarky_percent: f32,
+    pub self_consumption_percent: f32,
+    /// Only while charging
+    #[serde(default)]
+    pub time_to_full_minutes: Option<u32>,
+    /// Only while discharging
+    #[serde(default)]
+    pub time_to_empty_minutes: Option<u32>,
+    /// House consumption from the external meter, when one is configured
+    #[serde(default)]
+    pub consumption_external: Option<u16>,
+    /// External meter minus inverter consumption in W
+    #[serde(default)]
+    pub consumption_discrepancy: Option<i32>,
+    /// DC production next to the AC `full_production`, only with
+    /// `production_source = "dc"` or `"both"`
+    #[serde(default)]
+    pub production_dc: Option<u16>,
+    /// Decides which production is published, `full_production` stays AC
+    #[serde(default)]
+    pub production_source: ProductionSource,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatteryLimits {
+    pub charge_limit: Option<u32>,
+    pub discharge_limit: Option<u32>,
+    pub limited: bool,
+}
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhasePower {
+    pub l1: i32,
+    pub l2: i32,
+    pub l3: i32,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DataHistory {
     pub grid_buy: u64,
     pub grid_sell: u64,
@@ -21,25 +60,85 @@ pub struct DataHistory {
     pub battery_loaded: u64,
     pub battery_discharge: u64,
     pub battery_cycles: u16,
+    /// PV energy used on-site instead of exported, in Wh
+    #[serde(default)]
+    pub self_consumed_energy: u64,
+    /// A counter went backwards since the previous cycle (firmware update,
+    /// meter swap), deltas across this reading are meaningless
+    #[serde(default)]
+    pub counter_reset: bool,
+}
+/// Unit of the energy values in the MQTT payload and discovery, the
+/// counters themselves are always Wh.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum EnergyUnit {
+    #[serde(rename = "Wh", alias = "wh")]
+    Wh,
+    #[default]
+    #[serde(rename = "kWh", alias = "kwh")]
+    KWh,
+    #[serde(rename = "MWh", alias = "mwh")]
+    MWh,
+}
+
+impl EnergyUnit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            EnergyUnit::Wh => "Wh",
+            EnergyUnit::KWh => "kWh",
+            EnergyUnit::MWh => "MWh",
+        }
+    }
+
+    /// A Wh counter expressed in this unit.
+    pub fn scale(&self, wh: u64) -> f64 {
+        match self {
+            EnergyUnit::Wh => wh as f64,
+            EnergyUnit::KWh => wh as f64 / 1_000.0,
+            EnergyUnit::MWh => wh as f64 / 1_000_000.0,
+        }
+    }
+}
+
+impl FromStr for EnergyUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "wh" => Ok(EnergyUnit::Wh),
+            "kwh" => Ok(EnergyUnit::KWh),
+            "mwh" => Ok(EnergyUnit::MWh),
+            other => Err(format!("unknown energy unit '{}'", other)),
+        }
+    }
 }
-#[derive(Debug, Default, Clone)]
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct BatteryStatus {
     pub battery_state: BatteryState,
     pub battery_percent: u8,
     pub battery_energy: f32,
+    /// °C, None when the inverter has no temperature channel
+    #[serde(default)]
+    pub battery_temperature: Option<i16>,
 }
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum BatteryState {
     Loading(u32),
     Discharging(u32),
     Full,
     #[default]
     Empty,
+    /// Neither charging nor discharging, with the charge in percent
+    Idle(u8),
 }
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub enum SupplyState {
     Surplus(u32),
     Demand(u32),
+    /// Connected, production and consumption cancel out exactly
+    Balanced,
+    /// Grid lost, only reported by the inverter's grid mode
     #[default]
     Offline,
 }
@@ -67,7 +166,7 @@ impl SensorValue for BatteryState {
         match self {
             BatteryState::Loading(power) => -(*power as i32), // negativ = laden
             BatteryState::Discharging(power) => *power as i32, // positiv = entladen
-            BatteryState::Full | BatteryState::Empty => 0,
+            BatteryState::Full | BatteryState::Empty | BatteryState::Idle(_) => 0,
         }
     }
 
@@ -77,6 +176,7 @@ impl SensorValue for BatteryState {
             BatteryState::Discharging(_) => "discharging".to_string(),
             BatteryState::Full => "full".to_string(),
             BatteryState::Empty => "empty".to_string(),
+            BatteryState::Idle(_) => "idle".to_string(),
         }
     }
 }
@@ -87,7 +187,7 @@ impl SensorValue for SupplyState {
         match self {
             SupplyState::Surplus(power) => -(*power as i32),
             SupplyState::Demand(power) => *power as i32,
-            SupplyState::Offline => 0,
+            SupplyState::Balanced | SupplyState::Offline => 0,
         }
     }
 
@@ -95,6 +195,7 @@ impl SensorValue for SupplyState {
         match self {
             SupplyState::Surplus(_) => "surplus".to_string(),
             SupplyState::Demand(_) => "demand".to_string(),
+            SupplyState::Balanced => "balanced".to_string(),
             SupplyState::Offline => "offline".to_string(),
         }
     }
@@ -106,60 +207,143 @@ pub trait MqttPayload {
 
 impl MqttPayload for ProcessedData {
     fn to_state_json(&self) -> serde_json::Value {
-        json!({
-            "pv_production": self.full_production,
+        let mut payload = json!({
+            "pv_production": self.published_production(),
             "supply_power": self.supply_state.power_value(),
             "battery_power": self.battery_status.battery_state.power_value(),
             "consumption": self.consumption,
             "battery_percent": self.battery_status.battery_percent,
-            "battery_energy_wh": self.battery_status.battery_energy as i32,
+            "battery_energy_wh": self.battery_status.battery_energy.round() as i64,
             "battery_state": self.battery_status.battery_state.state_string(),
             "supply_state": self.supply_state.state_string(),
+            "grid_power_l1": self.phase_power.l1,
+            "grid_power_l2": self.phase_power.l2,
+            "grid_power_l3": self.phase_power.l3,
+            "autarky_percent": (self.autarky_percent * 10.0).round() / 10.0,
+            "self_consumption_percent": (self.self_consumption_percent * 10.0).round() / 10.0,
+            "time_to_full_minutes": self.time_to_full_minutes,
+            "time_to_empty_minutes": self.time_to_empty_minutes,
             "timestamp": chrono::Utc::now().to_rfc3339()
-        })
+        });
+
+        if let Some(limits) = &self.battery_limits {
+            payload["battery_power_limited"] = json!(limits.limited);
+            payload["battery_charge_limit"] = json!(limits.charge_limit);
+            payload["battery_discharge_limit"] = json!(limits.discharge_limit);
+        }
+
+        if let Some(temperature) = self.battery_status.battery_temperature {
+            payload["battery_temperature"] = json!(temperature);
+        }
+
+        if let Some(from_grid) = self.battery_charging_from_grid {
+            payload["battery_charging_from_grid"] = json!(from_grid);
+        }
+
+        if let (ProductionSource::Both, Some(production_dc)) =
+            (self.production_source, self.production_dc)
+        {
+            payload["pv_production_ac"] = json!(self.full_production);
+            payload["pv_production_dc"] = json!(production_dc);
+        }
+
+        if let Some(external) = self.consumption_external {
+            payload["consumption_external"] = json!(external);
+            payload["consumption_discrepancy"] = json!(self.consumption_discrepancy);
+        }
+
+        payload
     }
 }
 
 impl MqttPayload for DataHistory {
     fn to_state_json(&self) -> serde_json::Value {
-        json!({
-            "grid_buy": self.grid_buy as f64 / 1000.0,
-            "grid_sell": self.grid_sell as f64 / 1000.0,
-            "production_energy": self.production_energy as f64 / 1000.0,
-            "consumption_energy": self.consumption_energy as f64 / 1000.0,
-            "battery_loaded": self.battery_loaded as f64 / 1000.0,
-            "battery_discharge": self.battery_discharge as f64 / 1000.0,
-            "battery_cycles": self.battery_cycles,
-            "timestamp": chrono::Utc::now().to_rfc3339()
-        })
+        self.to_state_json_with_unit(EnergyUnit::KWh)
     }
 }
 
+/// Power fields of the state payload in W (or Wh), only published again
+/// once they moved more than the deadband.
+const POWER_FIELDS: [&str; 12] = [
+    "pv_production",
+    "pv_production_ac",
+    "pv_production_dc",
+    "supply_power",
+    "battery_power",
+    "consumption",
+    "battery_energy_wh",
+    "grid_power_l1",
+    "grid_power_l2",
+    "grid_power_l3",
+    "battery_charge_limit",
+    "battery_discharge_limit",
+];
+
 impl ProcessedData {
+    /// `pv_production` as published, DC with `production_source = "dc"`.
+    pub fn published_production(&self) -> u16 {
+        match (self.production_source, self.production_dc) {
+            (ProductionSource::Dc, Some(production_dc)) => production_dc,
+            _ => self.full_production,
+        }
+    }
+
+    /// `to_state_json` reduced to the fields that changed since `previous`,
+    /// power fields only when they moved more than `deadband_w`. The
+    /// timestamp is always kept.
+    pub fn changed_state_json(
+        &self,
+        previous: &ProcessedData,
+        deadband_w: u32,
+    ) -> serde_json::Value {
+        let mut payload = self.to_state_json();
+        let previous = previous.to_state_json();
+        if let Some(fields) = payload.as_object_mut() {
+            fields.retain(|key, value| {
+                let before = &previous[key.as_str()];
+                match (value.as_f64(), before.as_f64()) {
+                    _ if key == "timestamp" => true,
+                    (Some(now), Some(before)) if POWER_FIELDS.contains(&key.as_str()) => {
+                        (now - before).abs() > deadband_w as f64
+                    }
+                    _ => value != before,
+                }
+            });
+        }
+        payload
+    }
+
+    /// Blends the consumption of a separate meter into the reading. The
+    /// discrepancy is taken against the inverter value, with `Replace` the
+    /// external value becomes `consumption` and the autarky follows it.
+    pub fn apply_external_consumption(&mut self, external: u16, mode: ExternalMeterMode) {
+        self.consumption_external = Some(external);
+        self.consumption_discrepancy = Some(external as i32 - self.consumption as i32);
+        if mode == ExternalMeterMode::Replace {
+            self.consumption = external;
+            self.autarky_percent = autarky_percent(external, &self.supply_state);
+        }
+    }
+
+    /// False only while the inverter reports off-grid mode, see
+    /// `SupplyState::Offline`.
+    pub fn grid_connected(&self) -> bool {
+        !matches!(self.supply_state, SupplyState::Offline)
+    }
+
     pub fn process_raw(raw_data: RawPVData, config: &config::BatteryConfig) -> Self {
         let grid_power = raw_data.power_data.grid_power;
         let battery_power = raw_data.power_data.battery_power;
         let battery_percent = raw_data.power_data.battery_state;
-        let battery_threshold: u8 = config.empty_threshold;
         let max_battery_cap = config.max_battery_energy;
 
-        let supply_state = match grid_power.cmp(&0) {
-            Ordering::Less => SupplyState::Surplus(grid_power.abs().try_into().unwrap()),
-            Ordering::Greater => SupplyState::Demand(grid_power as u32),
-            Ordering::Equal => SupplyState::Offline,
-        };
-
-        let battery_state = match battery_power {
-            100.. => BatteryState::Discharging(battery_power.try_into().unwrap()),
-            ..-100 => BatteryState::Loading(battery_power.abs().try_into().unwrap()),
-            -100..100 => {
-                if battery_percent <= battery_threshold {
-                    BatteryState::Empty
-                } else {
-                    BatteryState::Full
-                }
-            }
-        };
+        let supply_state = supply_state(raw_data.power_data.grid_connected, grid_power);
+        let battery_state = battery_state(
+            battery_power,
+            battery_percent,
+            config.empty_threshold,
+            config.full_threshold,
+        );
 
         let percent = battery_percent as f32 / 100.0;
 
@@ -171,31 +355,297 @@ impl ProcessedData {
             battery_state,
             battery_percent,
             battery_energy,
+            battery_temperature: raw_data.power_data.battery_temperature,
+        };
+
+        let battery_limits = if config.power_limit_detection {
+            BatteryLimits::evaluate(
+                battery_power,
+                raw_data.power_data.battery_charge_limit,
+                raw_data.power_data.battery_discharge_limit,
+            )
+        } else {
+            None
         };
 
+        let battery_charging_from_grid = config
+            .grid_charge_detection
+            .then(|| charging_from_grid(&battery_status.battery_state, &supply_state));
+
+        // DC only replaces the published value, the derivations, metrics
+        // and stored rows keep the AC output
+        let production = raw_data.power_data.production_power;
+        let production_dc = match config.production_source {
+            ProductionSource::Ac => None,
+            ProductionSource::Dc | ProductionSource::Both => Some(raw_data.power_data.dc_power),
+        };
+        let consumption = match config.consumption_mode {
+            ConsumptionMode::Direct => raw_data.power_data.consumption_power,
+            ConsumptionMode::Derived => {
+                derived_consumption(production, raw_data.power_data.grid_power, battery_power)
+            }
+        };
+        let autarky_percent = autarky_percent(consumption, &supply_state);
+        let self_consumption_percent = self_consumption_percent(production, &supply_state);
+        let (time_to_full_minutes, time_to_empty_minutes) =
+            battery_time_estimates(&battery_status, config);
+
         ProcessedData {
             supply_state,
             battery_status,
-            full_production: raw_data.power_data.production_power,
-            consumption: raw_data.power_data.consumption_power,
+            battery_limits,
+            battery_charging_from_grid,
+            autarky_percent,
+            self_consumption_percent,
+            time_to_full_minutes,
+            time_to_empty_minutes,
+            consumption_external: None,
+            consumption_discrepancy: None,
+            production_dc,
+            production_source: config.production_source,
+            full_production: production,
+            consumption,
+            phase_power: PhasePower {
+                l1: raw_data.power_data.grid_power_l1,
+                l2: raw_data.power_data.grid_power_l2,
+                l3: raw_data.power_data.grid_power_l3,
+            },
+        }
+    }
+}
+
+/// 0 W at the meter is a perfectly balanced house as well, only the grid
+/// mode tells a lost grid apart.
+pub fn supply_state(grid_connected: Option<bool>, grid_power: i32) -> SupplyState {
+    match (grid_connected, grid_power.cmp(&0)) {
+        (Some(false), _) => SupplyState::Offline,
+        (_, Ordering::Less) => SupplyState::Surplus(grid_power.unsigned_abs()),
+        (_, Ordering::Greater) => SupplyState::Demand(grid_power as u32),
+        (_, Ordering::Equal) => SupplyState::Balanced,
+    }
+}
+
+/// Within +/- 100 W the battery counts as idle: empty up to
+/// `empty_threshold`, full from `full_threshold` on, idle in between.
+pub fn battery_state(
+    battery_power: i32,
+    battery_percent: u8,
+    empty_threshold: u8,
+    full_threshold: u8,
+) -> BatteryState {
+    match battery_power {
+        100.. => BatteryState::Discharging(battery_power as u32),
+        ..-100 => BatteryState::Loading(battery_power.unsigned_abs()),
+        -100..100 => {
+            if battery_percent <= empty_threshold {
+                BatteryState::Empty
+            } else if battery_percent >= full_threshold {
+                BatteryState::Full
+            } else {
+                BatteryState::Idle(battery_percent)
+            }
+        }
+    }
+}
+
+/// The battery charges while the grid is importing. Part of the charge may
+/// still come from PV, that counts as grid charging as well.
+pub fn charging_from_grid(battery_state: &BatteryState, supply_state: &SupplyState) -> bool {
+    matches!(battery_state, BatteryState::Loading(_))
+        && matches!(supply_state, SupplyState::Demand(_))
+}
+
+/// Minutes until the battery is full while charging, or down to
+/// `empty_threshold` while discharging, at the current rate. The idle band
+/// of +/- 100 W gives neither.
+pub fn battery_time_estimates(
+    battery_status: &BatteryStatus,
+    config: &config::BatteryConfig,
+) -> (Option<u32>, Option<u32>) {
+    let capacity = config.max_battery_energy as f32;
+    let minutes =
+        |energy_wh: f32, power: u32| (energy_wh.max(0.0) / power as f32 * 60.0).round() as u32;
+
+    match battery_status.battery_state {
+        BatteryState::Loading(power) => (
+            Some(minutes(capacity - battery_status.battery_energy, power)),
+            None,
+        ),
+        BatteryState::Discharging(power) => {
+            let reserve = capacity * config.empty_threshold as f32 / 100.0;
+            (
+                None,
+                Some(minutes(battery_status.battery_energy - reserve, power)),
+            )
         }
+        BatteryState::Full | BatteryState::Empty | BatteryState::Idle(_) => (None, None),
+    }
+}
+
+/// Energy balance of the house: production + grid import - export +
+/// battery discharge - charge. Grid and battery power are signed like the
+/// inverter reports them, positive = import/discharge. A negative result
+/// means the signs of the setup do not match and is clamped to 0.
+fn derived_consumption(production: u16, grid_power: i32, battery_power: i32) -> u16 {
+    let consumption = production as i64 + grid_power as i64 + battery_power as i64;
+    if consumption < 0 {
+        warn!(
+            production,
+            grid_power,
+            battery_power,
+            consumption,
+            "Derived consumption is negative, check the sign of the grid and battery channels"
+        );
+    }
+    consumption.clamp(0, u16::MAX as i64) as u16
+}
+
+/// Share of the consumption not covered by grid import:
+/// (consumption - grid import) / consumption
+pub fn autarky_percent(consumption: u16, supply_state: &SupplyState) -> f32 {
+    if consumption == 0 {
+        return 0.0;
     }
+
+    let grid_import = match supply_state {
+        SupplyState::Demand(power) => *power as f32,
+        _ => 0.0,
+    };
+    let consumption = consumption as f32;
+
+    ((consumption - grid_import) / consumption * 100.0).clamp(0.0, 100.0)
+}
+
+/// Share of the production used on-site instead of exported:
+/// (production - grid export) / production
+pub fn self_consumption_percent(production: u16, supply_state: &SupplyState) -> f32 {
+    if production == 0 {
+        return 0.0;
+    }
+
+    let grid_export = match supply_state {
+        SupplyState::Surplus(power) => *power as f32,
+        _ => 0.0,
+    };
+    let production = production as f32;
+
+    ((production - grid_export) / production * 100.0).clamp(0.0, 100.0)
+}
+
+impl BatteryLimits {
+    /// Compares the battery power with the allowed limit in its current direction.
+    /// Returns `None` when the inverter exposes neither limit channel.
+    pub fn evaluate(
+        battery_power: i32,
+        charge_limit: Option<u32>,
+        discharge_limit: Option<u32>,
+    ) -> Option<Self> {
+        if charge_limit.is_none() && discharge_limit.is_none() {
+            return None;
+        }
+
+        // battery_power: negativ = laden, positiv = entladen
+        let limited = match battery_power.cmp(&0) {
+            Ordering::Less => {
+                charge_limit.is_some_and(|limit| battery_power.unsigned_abs() >= limit)
+            }
+            Ordering::Greater => {
+                discharge_limit.is_some_and(|limit| battery_power.unsigned_abs() >= limit)
+            }
+            Ordering::Equal => false,
+        };
+
+        Some(BatteryLimits {
+            charge_limit,
+            discharge_limit,
+            limited,
+        })
+    }
+}
+
+/// Equivalent full cycles: one cycle is the nominal capacity discharged once,
+/// so 20000 Wh out of a 10000 Wh battery are 2 cycles.
+///
+/// cycles = total discharge [Wh] / max_battery_energy [Wh]
+///
+/// `empty_threshold` is a percentage of the capacity and only used for the
+/// battery state, it is not part of the cycle count. Returns 0 without a
+/// configured capacity.
+fn equivalent_full_cycles(battery_discharge: u64, max_battery_energy: u16) -> u16 {
+    if max_battery_energy == 0 {
+        return 0;
+    }
+
+    let cycles = battery_discharge / max_battery_energy as u64;
+    cycles.min(u16::MAX as u64) as u16
 }
 
 impl DataHistory {
-    pub fn process_raw(raw_data: RawPVData, config: &config::BatteryConfig) -> Self {
-        let battery_cycles = (raw_data.energy_data.battery_discharge as f32
-            / (config.max_battery_energy as f32
-                * ((100.0 - config.empty_threshold as f32) / 100.0)))
-            as u16;
+    pub fn to_state_json_with_unit(&self, unit: EnergyUnit) -> serde_json::Value {
+        json!({
+            "grid_buy": unit.scale(self.grid_buy),
+            "grid_sell": unit.scale(self.grid_sell),
+            "production_energy": unit.scale(self.production_energy),
+            "consumption_energy": unit.scale(self.consumption_energy),
+            "battery_loaded": unit.scale(self.battery_loaded),
+            "battery_discharge": unit.scale(self.battery_discharge),
+            "battery_cycles": self.battery_cycles,
+            "self_consumed_energy": unit.scale(self.self_consumed_energy),
+            "unit": unit.symbol(),
+            "counter_reset": self.counter_reset,
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })
+    }
+
+    /// The discharge register counts energy leaving the cells, before the
+    /// inverter's conversion losses. With a round-trip efficiency below 1.0 the
+    /// stored discharge is the energy actually delivered:
+    ///
+    /// battery_discharge = raw_discharge * battery_efficiency
+    ///
+    /// Battery cycles are still derived from the raw cell throughput.
+    ///
+    /// `previous` is the raw energy of the last cycle, any counter below its
+    /// previous value marks the reading as `counter_reset`.
+    pub fn process_raw(
+        raw_data: RawPVData,
+        config: &config::BatteryConfig,
+        previous: Option<&RawEnergyData>,
+    ) -> Self {
+        let counter_reset = match previous {
+            Some(previous) => {
+                let reset = decreased_counters(previous, &raw_data.energy_data);
+                if !reset.is_empty() {
+                    warn!(
+                        counters = %reset.join(", "),
+                        "Energy counter went backwards, series is discontinuous"
+                    );
+                }
+                !reset.is_empty()
+            }
+            None => false,
+        };
+
+        let battery_cycles = equivalent_full_cycles(
+            raw_data.energy_data.battery_discharge,
+            config.max_battery_energy,
+        );
 
         let grid_buy = raw_data.energy_data.grid_buy;
         let grid_sell = raw_data.energy_data.grid_sell;
         let production_energy = raw_data.energy_data.production_energy;
         let consumption_energy = raw_data.energy_data.consumption_energy;
         let battery_loaded = raw_data.energy_data.battery_loading;
-        let battery_discharge = raw_data.energy_data.battery_discharge;
+        let battery_discharge = if config.battery_efficiency < 1.0 {
+            (raw_data.energy_data.battery_discharge as f64 * config.battery_efficiency as f64)
+                .round() as u64
+        } else {
+            raw_data.energy_data.battery_discharge
+        };
         warn!("Batter loaded: {battery_loaded}");
+        // Exported energy can exceed the PV counter when the battery feeds in
+        let self_consumed_energy = production_energy.saturating_sub(grid_sell);
+
         DataHistory {
             grid_buy,
             grid_sell,
@@ -204,6 +654,40 @@ impl DataHistory {
             battery_loaded,
             battery_discharge,
             battery_cycles,
+            self_consumed_energy,
+            counter_reset,
         }
     }
 }
+
+/// Names of the energy counters that are lower than in `previous`.
+fn decreased_counters(previous: &RawEnergyData, current: &RawEnergyData) -> Vec<&'static str> {
+    [
+        ("grid_buy", previous.grid_buy, current.grid_buy),
+        ("grid_sell", previous.grid_sell, current.grid_sell),
+        (
+            "battery_loading",
+            previous.battery_loading,
+            current.battery_loading,
+        ),
+        (
+            "battery_discharge",
+            previous.battery_discharge,
+            current.battery_discharge,
+        ),
+        (
+            "production_energy",
+            previous.production_energy,
+            current.production_energy,
+        ),
+        (
+            "consumption_energy",
+            previous.consumption_energy,
+            current.consumption_energy,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, before, now)| now < before)
+    .map(|(name, _, _)| name)
+    .collect()
+}
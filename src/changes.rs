@@ -0,0 +1,129 @@
+use crate::calculator::{MqttPayload, ProcessedData};
+use crate::config::ChangeEventConfig;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub previous: Value,
+    pub current: Value,
+}
+
+/// Tracks the last reported value per field and reports only the fields that
+/// moved by more than the configured delta since they were last reported.
+#[derive(Debug, Clone)]
+pub struct ChangeDetector {
+    config: ChangeEventConfig,
+    last_reported: HashMap<String, Value>,
+}
+
+impl ChangeDetector {
+    pub fn new(config: ChangeEventConfig) -> Self {
+        Self {
+            config,
+            last_reported: HashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Compares the new reading against the baseline. The very first reading
+    /// only seeds the baseline, since the full snapshot is published anyway.
+    pub fn detect(&mut self, data: &ProcessedData) -> Vec<FieldChange> {
+        let snapshot = data.to_state_json();
+        let Some(fields) = snapshot.as_object() else {
+            return Vec::new();
+        };
+
+        let mut changes = Vec::new();
+
+        for (field, current) in fields {
+            if field == "timestamp" {
+                continue;
+            }
+
+            match self.last_reported.get(field) {
+                Some(previous) if !self.exceeds_delta(field, previous, current) => {}
+                Some(previous) => {
+                    changes.push(FieldChange {
+                        field: field.clone(),
+                        previous: previous.clone(),
+                        current: current.clone(),
+                    });
+                    self.last_reported.insert(field.clone(), current.clone());
+                }
+                None => {
+                    self.last_reported.insert(field.clone(), current.clone());
+                }
+            }
+        }
+
+        changes
+    }
+
+    fn exceeds_delta(&self, field: &str, previous: &Value, current: &Value) -> bool {
+        match (previous.as_f64(), current.as_f64()) {
+            (Some(previous), Some(current)) => {
+                let delta = if field.ends_with("_percent") {
+                    self.config.percent_delta as f64
+                } else {
+                    self.config.power_delta_w as f64
+                };
+                (current - previous).abs() > delta
+            }
+            // State strings and other non-numeric values report on any change
+            _ => previous != current,
+        }
+    }
+}
+
+pub fn changes_payload(changes: &[FieldChange]) -> Value {
+    json!({
+        "changes": changes,
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    })
+}
+
+#[test]
+fn test_only_changed_field_is_reported() {
+    use crate::calculator::{BatteryState, BatteryStatus, SupplyState};
+
+    let mut detector = ChangeDetector::new(ChangeEventConfig {
+        enabled: true,
+        power_delta_w: 100,
+        percent_delta: 1,
+    });
+
+    let first = ProcessedData {
+        supply_state: SupplyState::Demand(200),
+        battery_status: BatteryStatus {
+            battery_state: BatteryState::Discharging(300),
+            battery_percent: 50,
+            battery_energy: 5000.0,
+            battery_temperature: None,
+        },
+        full_production: 1000,
+        consumption: 1500,
+        ..Default::default()
+    };
+
+    assert!(detector.detect(&first).is_empty());
+
+    let mut second = first.clone();
+    second.full_production = 1250;
+
+    let changes = detector.detect(&second);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].field, "pv_production");
+    assert_eq!(changes[0].previous, 1000);
+    assert_eq!(changes[0].current, 1250);
+
+    // Small drift below the delta is not reported
+    let mut third = second.clone();
+    third.consumption = 1550;
+    assert!(detector.detect(&third).is_empty());
+}
@@ -1,38 +1,126 @@
+use crate::config::{ChannelMap, PlausibilityLimits, PvAuth};
 use color_eyre::Result;
 use color_eyre::eyre::eyre;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error};
-
-const DC_POWER_PATH: &str = "_sum/ProductionDcActualPower";
-const PRODUCTION_POWER_PATH: &str = "_sum/ProductionActivePower";
-const PRODUCTION_ENERGY_PATH: &str = "_sum/ProductionActiveEnergy";
-const GRID_POWER_PATH: &str = "_sum/GridActivePower";
-const GRID_BUY_PATH: &str = "_sum/GridBuyActiveEnergy";
-const GRID_SELL_PATH: &str = "_sum/GridSellActiveEnergy";
-const BATTERY_STATE_PATH: &str = "_sum/EssSoc";
-const BATTERY_POWER_PATH: &str = "_sum/EssActivePower";
-const BATTERY_LOADING_PATH: &str = "_sum/EssDcChargeEnergy";
-const BATTERY_DISCHARGE_PATH: &str = "_sum/EssDcDischargeEnergy";
-pub const CONSUMPTION_POWER_PATH: &str = "_sum/ConsumptionActivePower";
-const CONSUMPTION_ENERGY_PATH: &str = "_sum/ConsumptionActiveEnergy";
-
-const PATH_POWER_ARR: [&str; 6] = [
-    DC_POWER_PATH,
-    PRODUCTION_POWER_PATH,
-    GRID_POWER_PATH,
-    BATTERY_STATE_PATH,
-    BATTERY_POWER_PATH,
-    CONSUMPTION_POWER_PATH,
-];
-
-const PATH_ENERGY_ARR: [&str; 6] = [
-    GRID_BUY_PATH,
-    GRID_SELL_PATH,
-    PRODUCTION_ENERGY_PATH,
-    CONSUMPTION_ENERGY_PATH,
-    BATTERY_LOADING_PATH,
-    BATTERY_DISCHARGE_PATH,
-];
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex, Once, RwLock};
+use tracing::{debug, error, info, warn};
+
+/// Stores one channel reading in the collected data.
+type Setter<T> = fn(&mut T, &RawPVMessage);
+
+fn power_channels(channels: &ChannelMap) -> [(&'static str, &str, Setter<RawPowerData>); 6] {
+    [
+        ("dc_power", &channels.dc_power, |data, msg| {
+            data.dc_power = msg.clamped(0, u16::MAX)
+        }),
+        (
+            "production_power",
+            &channels.production_power,
+            |data, msg| data.production_power = msg.clamped(0, u16::MAX),
+        ),
+        ("grid_power", &channels.grid_power, |data, msg| {
+            data.grid_power = msg.clamped(i32::MIN, i32::MAX)
+        }),
+        ("battery_soc", &channels.battery_soc, |data, msg| {
+            data.battery_state = msg.clamped(0, 100)
+        }),
+        ("battery_power", &channels.battery_power, |data, msg| {
+            data.battery_power = msg.clamped(i32::MIN, i32::MAX)
+        }),
+        (
+            "consumption_power",
+            &channels.consumption_power,
+            |data, msg| data.consumption_power = msg.clamped(0, u16::MAX),
+        ),
+    ]
+}
+
+// Per-phase channels are optional, not every setup exposes them
+fn phase_channels(channels: &ChannelMap) -> [(&str, Setter<RawPowerData>); 3] {
+    [
+        (&channels.grid_power_l1, |data, msg| {
+            data.grid_power_l1 = msg.clamped(i32::MIN, i32::MAX)
+        }),
+        (&channels.grid_power_l2, |data, msg| {
+            data.grid_power_l2 = msg.clamped(i32::MIN, i32::MAX)
+        }),
+        (&channels.grid_power_l3, |data, msg| {
+            data.grid_power_l3 = msg.clamped(i32::MIN, i32::MAX)
+        }),
+    ]
+}
+
+// Allowed charge/discharge power, only exposed by managed ESS. The allowed
+// charge power is reported negative.
+fn limit_channels(channels: &ChannelMap) -> [(&str, Setter<RawPowerData>); 2] {
+    [
+        (&channels.battery_charge_limit, |data, msg| {
+            data.battery_charge_limit =
+                Some(u32::try_from(msg.value.unsigned_abs()).unwrap_or(u32::MAX))
+        }),
+        (&channels.battery_discharge_limit, |data, msg| {
+            data.battery_discharge_limit =
+                Some(u32::try_from(msg.value.unsigned_abs()).unwrap_or(u32::MAX))
+        }),
+    ]
+}
+
+/// OpenEMS grid mode: 1 = on-grid, 2 = off-grid, 0 = undefined
+fn grid_mode_connected(value: i64) -> Option<bool> {
+    match value {
+        1 => Some(true),
+        2 => Some(false),
+        _ => None,
+    }
+}
+
+fn energy_channels(channels: &ChannelMap) -> [(&'static str, &str, Setter<RawEnergyData>); 6] {
+    [
+        ("grid_buy_energy", &channels.grid_buy_energy, |data, msg| {
+            data.grid_buy = msg.energy_wh()
+        }),
+        (
+            "grid_sell_energy",
+            &channels.grid_sell_energy,
+            |data, msg| data.grid_sell = msg.energy_wh(),
+        ),
+        (
+            "production_energy",
+            &channels.production_energy,
+            |data, msg| data.production_energy = msg.energy_wh(),
+        ),
+        (
+            "consumption_energy",
+            &channels.consumption_energy,
+            |data, msg| data.consumption_energy = msg.energy_wh(),
+        ),
+        (
+            "battery_charge_energy",
+            &channels.battery_charge_energy,
+            |data, msg| data.battery_loading = msg.energy_wh(),
+        ),
+        (
+            "battery_discharge_energy",
+            &channels.battery_discharge_energy,
+            |data, msg| data.battery_discharge = msg.energy_wh(),
+        ),
+    ]
+}
+
+// Shared by every request so keep-alive connections to the inverter are reused
+static HTTP_CLIENT: LazyLock<RwLock<reqwest::Client>> =
+    LazyLock::new(|| RwLock::new(reqwest::Client::new()));
+static HTTP_CLIENT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// Next line per (recording, channel) of a `file://` NDJSON replay
+static REPLAY_POSITIONS: LazyLock<Mutex<HashMap<(String, String), usize>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static MISSING_PHASE_WARNING: Once = Once::new();
+static MISSING_GRID_MODE_WARNING: Once = Once::new();
+static UNKNOWN_ENERGY_UNIT_WARNING: Once = Once::new();
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct RawPVMessage {
@@ -43,10 +131,40 @@ pub struct RawPVMessage {
     pub access_mode: String,
     pub text: String,
     pub unit: String,
+    #[serde(deserialize_with = "deserialize_channel_value")]
     pub value: i64,
 }
 
-#[derive(Default, Debug, Clone)]
+/// Shapes FEMS has been seen to report a channel value in.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChannelValue {
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// Accepts integers, floats (truncated) and numeric strings, so one odd
+/// channel does not fail the whole cycle. `null` is an error, a missing value
+/// must not turn into 0.
+fn deserialize_channel_value<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<ChannelValue>::deserialize(deserializer)? {
+        Some(ChannelValue::Integer(value)) => Ok(value),
+        Some(ChannelValue::Float(value)) => Ok(value as i64),
+        Some(ChannelValue::Text(text)) => {
+            let text = text.trim();
+            text.parse::<i64>()
+                .or_else(|_| text.parse::<f64>().map(|value| value as i64))
+                .map_err(|_| serde::de::Error::custom(format!("non-numeric value '{}'", text)))
+        }
+        None => Err(serde::de::Error::custom("channel reported no value")),
+    }
+}
+
+#[derive(Default, Debug, PartialEq, Clone)]
 pub struct RawPVData {
     pub energy_data: RawEnergyData,
     pub power_data: RawPowerData,
@@ -60,6 +178,15 @@ pub struct RawPowerData {
     pub battery_state: u8,
     pub battery_power: i32,
     pub consumption_power: u16,
+    pub grid_power_l1: i32,
+    pub grid_power_l2: i32,
+    pub grid_power_l3: i32,
+    pub battery_charge_limit: Option<u32>,
+    pub battery_discharge_limit: Option<u32>,
+    /// None when the inverter does not expose the grid mode channel
+    pub grid_connected: Option<bool>,
+    /// °C, None when the inverter does not expose the temperature channel
+    pub battery_temperature: Option<i16>,
 }
 #[derive(Default, Debug, PartialEq, Clone)]
 pub struct RawEnergyData {
@@ -72,30 +199,16 @@ pub struct RawEnergyData {
 }
 
 impl RawPowerData {
-    pub async fn get_data(base_path: &str) -> Result<Self> {
+    pub async fn get_data(base_path: &str, channels: &ChannelMap, auth: &PvAuth) -> Result<Self> {
         let mut raw_power_data = RawPowerData::default();
-        for path in PATH_POWER_ARR {
-            let url = format!("{:0}/{:1}", base_path, path);
-            match send_request(url.as_str()).await {
-                Ok(response) => match response.address.as_str() {
-                    DC_POWER_PATH => raw_power_data.dc_power = response.value as u16,
-                    PRODUCTION_POWER_PATH => {
-                        raw_power_data.production_power = response.value as u16
-                    }
-                    GRID_POWER_PATH => raw_power_data.grid_power = response.value as i32,
-                    BATTERY_STATE_PATH => raw_power_data.battery_state = response.value as u8,
-                    BATTERY_POWER_PATH => raw_power_data.battery_power = response.value as i32,
-                    CONSUMPTION_POWER_PATH => {
-                        raw_power_data.consumption_power = response.value as u16
-                    }
-                    _ => panic!("Should not be possible"),
-                },
-                Err(e) => {
-                    error!("No working HTTP-Request could be resieved: {e}");
-                    return Err(e);
-                }
-            }
-        }
+        fill_channels(
+            &mut raw_power_data,
+            power_channels(channels),
+            base_path,
+            channels,
+            auth,
+        )
+        .await?;
         if raw_power_data == RawPowerData::default() {
             return Err(eyre!(
                 "No real data could be generated the http Request seams to be not working correctly"
@@ -103,38 +216,93 @@ impl RawPowerData {
         }
         raw_power_data.battery_power -= raw_power_data.dc_power as i32;
 
+        raw_power_data
+            .fill_phase_data(base_path, channels, auth)
+            .await;
+        raw_power_data
+            .fill_limit_data(base_path, channels, auth)
+            .await;
+        raw_power_data
+            .fill_grid_mode(base_path, channels, auth)
+            .await;
+        raw_power_data
+            .fill_battery_temperature(base_path, channels, auth)
+            .await;
+
         Ok(raw_power_data)
     }
-}
 
-impl RawEnergyData {
-    pub async fn get_data(base_path: &str) -> Result<Self> {
-        let mut raw_energy_data = RawEnergyData::default();
-        for path in PATH_ENERGY_ARR {
+    async fn fill_phase_data(&mut self, base_path: &str, channels: &ChannelMap, auth: &PvAuth) {
+        for (path, set) in phase_channels(channels) {
             let url = format!("{:0}/{:1}", base_path, path);
-            match send_request(url.as_str()).await {
-                Ok(response) => match response.address.as_str() {
-                    PRODUCTION_ENERGY_PATH => {
-                        raw_energy_data.production_energy = response.value as u64
-                    }
-                    GRID_BUY_PATH => raw_energy_data.grid_buy = response.value as u64,
-                    GRID_SELL_PATH => raw_energy_data.grid_sell = response.value as u64,
-                    BATTERY_LOADING_PATH => raw_energy_data.battery_loading = response.value as u64,
-                    BATTERY_DISCHARGE_PATH => {
-                        raw_energy_data.battery_discharge = response.value as u64
-                    }
-                    CONSUMPTION_ENERGY_PATH => {
-                        raw_energy_data.consumption_energy = response.value as u64
-                    }
-                    _ => panic!("Should not be possible"),
-                },
+            match send_request(url.as_str(), auth).await {
+                Ok(response) => set(self, &response),
+                Err(e) => {
+                    MISSING_PHASE_WARNING.call_once(|| {
+                        warn!("Per-phase grid channel {path} not available, using 0: {e}");
+                    });
+                }
+            }
+        }
+    }
 
+    async fn fill_limit_data(&mut self, base_path: &str, channels: &ChannelMap, auth: &PvAuth) {
+        for (path, set) in limit_channels(channels) {
+            let url = format!("{:0}/{:1}", base_path, path);
+            match send_request(url.as_str(), auth).await {
+                Ok(response) => set(self, &response),
                 Err(e) => {
-                    error!("No working HTTP-Request could be resieved: {e}");
-                    return Err(e);
+                    debug!("Battery limit channel {path} not available: {e}");
                 }
             }
         }
+    }
+
+    async fn fill_grid_mode(&mut self, base_path: &str, channels: &ChannelMap, auth: &PvAuth) {
+        let url = format!("{:0}/{:1}", base_path, channels.grid_mode);
+        match send_request(url.as_str(), auth).await {
+            Ok(response) => self.grid_connected = grid_mode_connected(response.value),
+            Err(e) => {
+                MISSING_GRID_MODE_WARNING.call_once(|| {
+                    warn!(
+                        "Grid mode channel {} not available, grid loss is not detected: {e}",
+                        channels.grid_mode
+                    );
+                });
+            }
+        }
+    }
+
+    async fn fill_battery_temperature(
+        &mut self,
+        base_path: &str,
+        channels: &ChannelMap,
+        auth: &PvAuth,
+    ) {
+        let url = format!("{:0}/{:1}", base_path, channels.battery_temperature);
+        match send_request(url.as_str(), auth).await {
+            Ok(response) => self.battery_temperature = Some(response.clamped(i16::MIN, i16::MAX)),
+            Err(e) => {
+                debug!(
+                    "Battery temperature channel {} not available: {e}",
+                    channels.battery_temperature
+                );
+            }
+        }
+    }
+}
+
+impl RawEnergyData {
+    pub async fn get_data(base_path: &str, channels: &ChannelMap, auth: &PvAuth) -> Result<Self> {
+        let mut raw_energy_data = RawEnergyData::default();
+        fill_channels(
+            &mut raw_energy_data,
+            energy_channels(channels),
+            base_path,
+            channels,
+            auth,
+        )
+        .await?;
         if raw_energy_data == RawEnergyData::default() {
             return Err(eyre!(
                 "No real data could be generated the http Request seams to be not working correctly"
@@ -145,29 +313,481 @@ impl RawEnergyData {
     }
 }
 
+/// Required channels that answered without a usable value. The reading is
+/// dropped instead of being stored with 0 in their place.
+#[derive(Debug)]
+pub struct PartialDataError {
+    pub missing: Vec<String>,
+}
+
+impl std::fmt::Display for PartialDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Required channels without a usable value: {}",
+            self.missing.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for PartialDataError {}
+
+/// Reads the core channels into `data`. An unreachable inverter fails right
+/// away, a channel answering without a usable value (404, no value, not a
+/// number) only fails the reading if it is in `required`, otherwise it
+/// stays 0. Channels with an empty path are not read.
+async fn fill_channels<'a, T>(
+    data: &mut T,
+    list: impl IntoIterator<Item = (&'static str, &'a str, Setter<T>)>,
+    base_path: &str,
+    channels: &ChannelMap,
+    auth: &PvAuth,
+) -> Result<()> {
+    let mut missing = Vec::new();
+    for (name, path, set) in list {
+        if path.is_empty() {
+            continue;
+        }
+        let url = format!("{:0}/{:1}", base_path, path);
+        match send_request(url.as_str(), auth).await {
+            Ok(response) => set(data, &response),
+            Err(e) if e.is::<InverterAuthError>() || e.is::<reqwest::Error>() => {
+                error!("No working HTTP-Request could be resieved: {e}");
+                return Err(e);
+            }
+            Err(e) if channels.required.iter().any(|required| required == name) => {
+                error!(
+                    channel = name,
+                    path, "Required channel has no usable value: {e}"
+                );
+                missing.push(name.to_string());
+            }
+            Err(e) => warn!(
+                channel = name,
+                path, "Channel has no usable value, using 0: {e}"
+            ),
+        }
+    }
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(PartialDataError { missing }.into())
+    }
+}
+
+impl RawPVMessage {
+    /// Value limited to `min..=max` instead of wrapping with `as`, e.g. a
+    /// negative consumption becomes 0 rather than 65535. Clamped values are
+    /// logged, the plausibility check still rejects a reading at the upper
+    /// limit.
+    pub fn clamped<T>(&self, min: T, max: T) -> T
+    where
+        T: Copy + Into<i64> + TryFrom<i64>,
+    {
+        let value = self.value.clamp(min.into(), max.into());
+        if value != self.value {
+            warn!(
+                channel = %self.address,
+                raw = self.value,
+                clamped = value,
+                "Channel value out of range, clamping"
+            );
+        }
+        T::try_from(value).unwrap_or(min)
+    }
+
+    /// Energy counter normalized to Wh according to the channel's `unit`.
+    /// Unknown units are taken as Wh, negative counters as 0.
+    pub fn energy_wh(&self) -> u64 {
+        let factor = energy_unit_factor(&self.unit).unwrap_or_else(|| {
+            UNKNOWN_ENERGY_UNIT_WARNING.call_once(|| {
+                warn!(
+                    "Unknown energy unit '{}' on {}, assuming Wh",
+                    self.unit, self.address
+                );
+            });
+            1.0
+        });
+
+        (self.value.max(0) as f64 * factor).round() as u64
+    }
+}
+
+/// Wh per reported unit, e.g. "kWh" -> 1000 or "0.1 kWh" -> 100.
+fn energy_unit_factor(unit: &str) -> Option<f64> {
+    let (scale, unit) = match unit.trim().split_once(' ') {
+        Some((scale, unit)) => (scale.parse::<f64>().ok()?, unit.trim()),
+        None => (1.0, unit.trim()),
+    };
+
+    let base = match unit {
+        "" | "Wh" => 1.0,
+        "kWh" => 1_000.0,
+        "MWh" => 1_000_000.0,
+        _ => return None,
+    };
+
+    Some(scale * base)
+}
+
 impl RawPVData {
-    pub async fn fill_raw(base_path: &str) -> Result<Self> {
+    pub async fn fill_raw(base_path: &str, channels: &ChannelMap, auth: &PvAuth) -> Result<Self> {
         let (energy_res, power_res) = tokio::join!(
-            RawEnergyData::get_data(base_path),
-            RawPowerData::get_data(base_path)
+            RawEnergyData::get_data(base_path, channels, auth),
+            RawPowerData::get_data(base_path, channels, auth)
         );
 
-        if energy_res.is_ok() && power_res.is_ok() {
-            let raw_pv_data = RawPVData {
-                energy_data: energy_res.unwrap(),
-                power_data: power_res.unwrap(),
-            };
-            Ok(raw_pv_data)
+        // Keep the cause so an auth failure stays recognizable
+        match (energy_res, power_res) {
+            (Ok(energy_data), Ok(power_data)) => Ok(RawPVData {
+                energy_data,
+                power_data,
+            }),
+            (Err(e), _) | (_, Err(e)) => Err(e.wrap_err("Request Failed")),
+        }
+    }
+
+    /// Checks the power readings against `limits` and the SoC against
+    /// 0..=100. Every value out of range is listed in the error.
+    pub fn validate(&self, limits: &PlausibilityLimits) -> Result<(), Vec<String>> {
+        let power = &self.power_data;
+        let checks = [
+            (
+                "dc_power",
+                power.dc_power as i64,
+                limits.max_production_power,
+            ),
+            (
+                "production_power",
+                power.production_power as i64,
+                limits.max_production_power,
+            ),
+            (
+                "consumption_power",
+                power.consumption_power as i64,
+                limits.max_consumption_power,
+            ),
+            ("grid_power", power.grid_power as i64, limits.max_grid_power),
+            (
+                "grid_power_l1",
+                power.grid_power_l1 as i64,
+                limits.max_grid_power,
+            ),
+            (
+                "grid_power_l2",
+                power.grid_power_l2 as i64,
+                limits.max_grid_power,
+            ),
+            (
+                "grid_power_l3",
+                power.grid_power_l3 as i64,
+                limits.max_grid_power,
+            ),
+            (
+                "battery_power",
+                power.battery_power as i64,
+                limits.max_battery_power,
+            ),
+        ];
+
+        let mut problems: Vec<String> = checks
+            .iter()
+            .filter(|(_, value, max)| value.unsigned_abs() > *max as u64)
+            .map(|(name, value, max)| format!("{} {} W exceeds {} W", name, value, max))
+            .collect();
+
+        if power.battery_state > 100 {
+            problems.push(format!(
+                "battery_state {}% outside 0..=100",
+                power.battery_state
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
         } else {
-            Err(eyre!("Request Failed"))
+            Err(problems)
+        }
+    }
+}
+
+/// The inverter endpoint answered 401 Unauthorized, the `pv_auth`
+/// credentials are missing or wrong. Kept apart from connection errors so
+/// the operator is not sent looking for a network problem.
+#[derive(Debug)]
+pub struct InverterAuthError;
+
+impl std::fmt::Display for InverterAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Inverter endpoint rejected the credentials (401 Unauthorized), check pv_auth"
+        )
+    }
+}
+
+impl std::error::Error for InverterAuthError {}
+
+/// Rebuilds the shared HTTP client after `threshold` consecutive collection
+/// failures. Dropping the old client closes its connection pool, so a stale
+/// keep-alive connection to a rebooted inverter is not reused. A threshold of
+/// 0 disables the rebuild.
+#[derive(Debug, Clone)]
+pub struct HttpSelfHeal {
+    threshold: u32,
+    consecutive_failures: u32,
+}
+
+impl HttpSelfHeal {
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            threshold,
+            consecutive_failures: 0,
         }
     }
+
+    pub fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+    }
+
+    /// Returns true when the client was rebuilt.
+    pub fn record_failure(&mut self) -> bool {
+        if self.threshold == 0 {
+            return false;
+        }
+
+        self.consecutive_failures += 1;
+        if self.consecutive_failures < self.threshold {
+            return false;
+        }
+
+        info!(
+            failures = self.consecutive_failures,
+            "Rebuilding HTTP client after consecutive collection failures"
+        );
+        rebuild_http_client();
+        self.consecutive_failures = 0;
+        true
+    }
 }
 
-pub async fn send_request(path: &str) -> Result<RawPVMessage> {
-    let response = reqwest::get(path).await?.text().await?;
+pub fn rebuild_http_client() {
+    *HTTP_CLIENT.write().unwrap() = reqwest::Client::new();
+    HTTP_CLIENT_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Number of rebuilds since startup.
+pub fn http_client_generation() -> u64 {
+    HTTP_CLIENT_GENERATION.load(Ordering::SeqCst)
+}
+
+pub async fn send_request(path: &str, auth: &PvAuth) -> Result<RawPVMessage> {
+    if let Some(location) = path.strip_prefix("file://") {
+        return read_recorded(location).await;
+    }
+
+    let client = HTTP_CLIENT.read().unwrap().clone();
+    let request = match auth {
+        PvAuth::None => client.get(path),
+        PvAuth::Basic { user, password } => client.get(path).basic_auth(user, Some(password)),
+        PvAuth::Bearer { token } => client.get(path).bearer_auth(token),
+    };
+
+    let response = request.send().await?;
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(InverterAuthError.into());
+    }
+    if !response.status().is_success() {
+        return Err(eyre!("Channel request returned {}", response.status()));
+    }
+    let response = response.text().await?;
     debug!("{response}");
     let response = serde_json::from_str(&response)?;
 
     Ok(response)
 }
+
+/// Serves a `file://` base address from captured responses instead of the
+/// inverter, either a directory with one `<channel path>.json` per channel
+/// or an `.ndjson` recording with one response per line.
+async fn read_recorded(location: &str) -> Result<RawPVMessage> {
+    if let Some((recording, address)) = location.split_once(".ndjson/") {
+        return replay_recorded(&format!("{recording}.ndjson"), address).await;
+    }
+
+    let response = tokio::fs::read_to_string(format!("{location}.json")).await?;
+    Ok(serde_json::from_str(&response)?)
+}
+
+/// Each request returns the next recorded response of the channel, starting
+/// over at the end of the recording.
+async fn replay_recorded(recording: &str, address: &str) -> Result<RawPVMessage> {
+    let content = tokio::fs::read_to_string(recording).await?;
+    let mut responses = Vec::new();
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let message: RawPVMessage = serde_json::from_str(line)?;
+        if message.address == address {
+            responses.push(message);
+        }
+    }
+    if responses.is_empty() {
+        return Err(eyre!("Channel {address} not recorded in {recording}"));
+    }
+
+    let mut positions = REPLAY_POSITIONS.lock().unwrap();
+    let position = positions
+        .entry((recording.to_string(), address.to_string()))
+        .or_default();
+    let message = responses.swap_remove(*position % responses.len());
+    *position += 1;
+
+    Ok(message)
+}
+
+#[cfg(test)]
+fn plausible_reading() -> RawPVData {
+    RawPVData {
+        energy_data: RawEnergyData::default(),
+        power_data: RawPowerData {
+            dc_power: 3_000,
+            production_power: 4_200,
+            grid_power: -800,
+            battery_state: 64,
+            battery_power: 1_200,
+            consumption_power: 2_200,
+            grid_power_l1: -300,
+            grid_power_l2: -250,
+            grid_power_l3: -250,
+            ..Default::default()
+        },
+    }
+}
+
+#[test]
+fn test_validate_accepts_plausible_reading() {
+    assert_eq!(
+        plausible_reading().validate(&PlausibilityLimits::default()),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_validate_rejects_production_spike() {
+    let limits = PlausibilityLimits {
+        max_production_power: 10_000,
+        ..Default::default()
+    };
+    let mut raw = plausible_reading();
+    raw.power_data.production_power = 50_000;
+
+    let problems = raw.validate(&limits).unwrap_err();
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("production_power"));
+}
+
+#[test]
+fn test_validate_rejects_wrapped_consumption() {
+    let mut raw = plausible_reading();
+    // 4294967295 truncated to the u16 field
+    raw.power_data.consumption_power = u16::MAX;
+
+    let problems = raw.validate(&PlausibilityLimits::default()).unwrap_err();
+    assert!(problems[0].starts_with("consumption_power"));
+}
+
+#[test]
+fn test_validate_rejects_grid_power_in_both_directions() {
+    let limits = PlausibilityLimits {
+        max_grid_power: 20_000,
+        ..Default::default()
+    };
+    let mut raw = plausible_reading();
+    raw.power_data.grid_power = -25_000;
+    raw.power_data.grid_power_l2 = 21_000;
+
+    let problems = raw.validate(&limits).unwrap_err();
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].starts_with("grid_power -25000"));
+    assert!(problems[1].starts_with("grid_power_l2"));
+}
+
+#[test]
+fn test_validate_rejects_battery_power() {
+    let limits = PlausibilityLimits {
+        max_battery_power: 5_000,
+        ..Default::default()
+    };
+    let mut raw = plausible_reading();
+    raw.power_data.battery_power = -7_000;
+
+    let problems = raw.validate(&limits).unwrap_err();
+    assert!(problems[0].starts_with("battery_power"));
+
+    // Exactly at the limit is still fine
+    raw.power_data.battery_power = -5_000;
+    assert!(raw.validate(&limits).is_ok());
+}
+
+#[test]
+fn test_validate_rejects_soc_above_100() {
+    let mut raw = plausible_reading();
+    raw.power_data.battery_state = 255;
+
+    let problems = raw.validate(&PlausibilityLimits::default()).unwrap_err();
+    assert_eq!(
+        problems,
+        vec!["battery_state 255% outside 0..=100".to_string()]
+    );
+
+    raw.power_data.battery_state = 100;
+    assert!(raw.validate(&PlausibilityLimits::default()).is_ok());
+}
+
+#[test]
+fn test_out_of_range_values_are_clamped() {
+    let message = |address: &str, value: i64| RawPVMessage {
+        address: address.to_string(),
+        type_field: "INTEGER".to_string(),
+        access_mode: "RO".to_string(),
+        text: String::new(),
+        unit: "W".to_string(),
+        value,
+    };
+    let channels = ChannelMap::default();
+    let apply = |name: &str, value: i64| {
+        let mut data = RawPowerData::default();
+        let (_, path, set) = power_channels(&channels)
+            .into_iter()
+            .find(|(channel, _, _)| *channel == name)
+            .unwrap();
+        set(&mut data, &message(path, value));
+        data
+    };
+
+    // Negative readings no longer wrap to 65535
+    assert_eq!(apply("dc_power", -1).dc_power, 0);
+    assert_eq!(apply("production_power", -250).production_power, 0);
+    assert_eq!(apply("consumption_power", -1).consumption_power, 0);
+    assert_eq!(
+        apply("consumption_power", 70_000).consumption_power,
+        u16::MAX
+    );
+
+    // SoC outside 0..=100
+    assert_eq!(apply("battery_soc", 101).battery_state, 100);
+    assert_eq!(apply("battery_soc", 300).battery_state, 100);
+    assert_eq!(apply("battery_soc", -5).battery_state, 0);
+
+    // Signed fields keep their sign and only clamp beyond i32
+    assert_eq!(apply("grid_power", -1_500).grid_power, -1_500);
+    assert_eq!(apply("grid_power", i64::MIN).grid_power, i32::MIN);
+    assert_eq!(
+        apply("battery_power", 5_000_000_000).battery_power,
+        i32::MAX
+    );
+
+    // In range values are unchanged
+    assert_eq!(apply("battery_soc", 87).battery_state, 87);
+    assert_eq!(apply("production_power", 4_200).production_power, 4_200);
+}
@@ -0,0 +1,249 @@
+use crate::calculator::DataHistory;
+use crate::config::DailyTotalsConfig;
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use color_eyre::eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use tracing::{debug, warn};
+
+/// Lifetime counters at the start of a day, in Wh.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DailyBaseline {
+    pub date: NaiveDate,
+    pub production: u64,
+    pub consumption: u64,
+    pub grid_import: u64,
+    pub grid_export: u64,
+}
+
+impl DailyBaseline {
+    fn from_history(date: NaiveDate, history: &DataHistory) -> Self {
+        Self {
+            date,
+            production: history.production_energy,
+            consumption: history.consumption_energy,
+            grid_import: history.grid_buy,
+            grid_export: history.grid_sell,
+        }
+    }
+}
+
+/// Energy since local midnight, in Wh.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DailyEnergy {
+    pub production: u64,
+    pub consumption: u64,
+    pub grid_import: u64,
+    pub grid_export: u64,
+}
+
+/// Turns the lifetime counters into totals that reset at local midnight.
+/// The first reading of a day is compared against the last one of the day
+/// before, so the energy in between is counted for the new day. The
+/// baseline is written to disk whenever it moves, a restart during the day
+/// keeps counting from the same point.
+#[derive(Debug, Clone)]
+pub struct DailyTotals {
+    config: DailyTotalsConfig,
+    timezone: Tz,
+    baseline: Option<DailyBaseline>,
+    last: Option<(NaiveDate, DataHistory)>,
+}
+
+impl DailyTotals {
+    pub fn new(config: DailyTotalsConfig, timezone: Tz) -> Self {
+        Self {
+            config,
+            timezone,
+            baseline: None,
+            last: None,
+        }
+    }
+
+    /// Restores the persisted baseline. A missing or unreadable file starts
+    /// a new day with the next reading.
+    pub async fn load(config: DailyTotalsConfig, timezone: Tz) -> Self {
+        let mut totals = Self::new(config, timezone);
+        if !totals.is_enabled() {
+            return totals;
+        }
+
+        let path = &totals.config.path;
+        match tokio::fs::read(path).await {
+            Ok(content) => match serde_json::from_slice::<DailyBaseline>(&content) {
+                Ok(baseline) => totals.baseline = Some(baseline),
+                Err(e) => warn!(path = %path, error = %e, "Failed to parse daily baseline"),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(path = %path, error = %e, "Failed to read daily baseline"),
+        }
+        totals
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Returns whether a new baseline was taken and should be saved.
+    pub fn push<Z: TimeZone>(&mut self, now: DateTime<Z>, history: &DataHistory) -> bool {
+        let today = now.with_timezone(&self.timezone).date_naive();
+        let previous = self.last.replace((today, history.clone()));
+        if self.baseline.is_some_and(|baseline| baseline.date == today) {
+            return false;
+        }
+
+        let start = match &previous {
+            Some((date, previous)) if date.succ_opt() == Some(today) => previous,
+            _ => history,
+        };
+        self.baseline = Some(DailyBaseline::from_history(today, start));
+        true
+    }
+
+    /// A counter that went backwards since midnight counts as 0.
+    pub fn today(&self) -> DailyEnergy {
+        let (Some(baseline), Some((_, current))) = (&self.baseline, &self.last) else {
+            return DailyEnergy::default();
+        };
+
+        DailyEnergy {
+            production: current
+                .production_energy
+                .saturating_sub(baseline.production),
+            consumption: current
+                .consumption_energy
+                .saturating_sub(baseline.consumption),
+            grid_import: current.grid_buy.saturating_sub(baseline.grid_import),
+            grid_export: current.grid_sell.saturating_sub(baseline.grid_export),
+        }
+    }
+
+    /// Local midnight the current totals started at, None when midnight
+    /// falls into a DST gap of the zone.
+    pub fn last_reset(&self) -> Option<String> {
+        let midnight = self.baseline?.date.and_time(NaiveTime::MIN);
+        self.timezone
+            .from_local_datetime(&midnight)
+            .earliest()
+            .map(|time| time.to_rfc3339())
+    }
+
+    pub fn payload(&self) -> serde_json::Value {
+        let today = self.today();
+        // Published in kWh like the other energy sensors
+        json!({
+            "production_today_kwh": today.production as f64 / 1000.0,
+            "consumption_today_kwh": today.consumption as f64 / 1000.0,
+            "grid_import_today_kwh": today.grid_import as f64 / 1000.0,
+            "grid_export_today_kwh": today.grid_export as f64 / 1000.0,
+            "last_reset": self.last_reset(),
+            "timestamp": Utc::now().to_rfc3339()
+        })
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let Some(baseline) = &self.baseline else {
+            return Ok(());
+        };
+
+        let path = &self.config.path;
+        if let Some(parent) = Path::new(path).parent() {
+            tokio::fs::create_dir_all(parent).await.wrap_err_with(|| {
+                format!("Failed to create daily baseline directory for {}", path)
+            })?;
+        }
+
+        let tmp_path = format!("{}.tmp", path);
+        let content = serde_json::to_vec_pretty(baseline)?;
+
+        tokio::fs::write(&tmp_path, content)
+            .await
+            .wrap_err_with(|| format!("Failed to write daily baseline to {}", tmp_path))?;
+        tokio::fs::rename(&tmp_path, path)
+            .await
+            .wrap_err_with(|| format!("Failed to move daily baseline to {}", path))?;
+
+        debug!(path = %path, date = %baseline.date, "Daily baseline saved");
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_daily_totals_reset_at_midnight() {
+    let config = DailyTotalsConfig {
+        enabled: true,
+        path: "data/test_daily_totals.json".to_string(),
+    };
+    let _ = tokio::fs::remove_file(&config.path).await;
+
+    let reading = |production: u64, consumption: u64, grid_buy: u64, grid_sell: u64| DataHistory {
+        grid_buy,
+        grid_sell,
+        production_energy: production,
+        consumption_energy: consumption,
+        battery_loaded: 0,
+        battery_discharge: 0,
+        battery_cycles: 0,
+        self_consumed_energy: 0,
+        counter_reset: false,
+    };
+    let at = |time: &str| time.parse::<DateTime<Utc>>().unwrap();
+
+    let mut totals = DailyTotals::load(config.clone(), Tz::Europe__Berlin).await;
+    assert!(totals.push(
+        at("2026-03-09T20:00:00Z"),
+        &reading(10_000, 5_000, 2_000, 1_000)
+    ));
+    assert_eq!(totals.today(), DailyEnergy::default());
+
+    // 23:30 local time, still the same day
+    assert!(!totals.push(
+        at("2026-03-09T22:30:00Z"),
+        &reading(11_500, 5_800, 2_400, 1_900)
+    ));
+    assert_eq!(totals.today().production, 1_500);
+    assert_eq!(totals.today().grid_export, 900);
+
+    // 00:10 local time: the totals start over from the last reading of yesterday
+    assert!(totals.push(
+        at("2026-03-09T23:10:00Z"),
+        &reading(11_800, 6_000, 2_500, 1_900)
+    ));
+    assert_eq!(
+        totals.today(),
+        DailyEnergy {
+            production: 300,
+            consumption: 200,
+            grid_import: 100,
+            grid_export: 0,
+        }
+    );
+    let payload = totals.payload();
+    assert_eq!(payload["production_today_kwh"], 0.3);
+    assert_eq!(payload["consumption_today_kwh"], 0.2);
+    assert_eq!(payload["last_reset"], "2026-03-10T00:00:00+01:00");
+    totals.save().await.unwrap();
+
+    // Restart during the day keeps the persisted baseline
+    let mut restarted = DailyTotals::load(config.clone(), Tz::Europe__Berlin).await;
+    assert!(!restarted.push(
+        at("2026-03-10T08:00:00Z"),
+        &reading(14_500, 8_000, 2_600, 3_000)
+    ));
+    assert_eq!(restarted.today().production, 3_000);
+    assert_eq!(restarted.today().grid_export, 1_100);
+
+    // Restart on a later day takes the first reading as baseline
+    let mut next_day = DailyTotals::load(config, Tz::Europe__Berlin).await;
+    assert!(next_day.push(
+        at("2026-03-12T08:00:00Z"),
+        &reading(20_000, 9_000, 2_700, 4_000)
+    ));
+    assert_eq!(next_day.today(), DailyEnergy::default());
+    assert_eq!(
+        next_day.last_reset().as_deref(),
+        Some("2026-03-12T00:00:00+01:00")
+    );
+}
@@ -0,0 +1,157 @@
+use crate::calculator::DataHistory;
+use crate::config::EfficiencyConfig;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use std::collections::VecDeque;
+
+/// Energy moved through the system between two counter snapshots, in Wh.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnergyDelta {
+    pub production: u64,
+    pub grid_buy: u64,
+    pub grid_sell: u64,
+    pub consumption: u64,
+    pub battery_loaded: u64,
+    pub battery_discharge: u64,
+}
+
+impl EnergyDelta {
+    pub fn between(older: &DataHistory, newer: &DataHistory) -> Self {
+        Self {
+            production: newer
+                .production_energy
+                .saturating_sub(older.production_energy),
+            grid_buy: newer.grid_buy.saturating_sub(older.grid_buy),
+            grid_sell: newer.grid_sell.saturating_sub(older.grid_sell),
+            consumption: newer
+                .consumption_energy
+                .saturating_sub(older.consumption_energy),
+            battery_loaded: newer.battery_loaded.saturating_sub(older.battery_loaded),
+            battery_discharge: newer
+                .battery_discharge
+                .saturating_sub(older.battery_discharge),
+        }
+    }
+
+    /// efficiency = (loads + export + battery charge) / (PV + grid import + battery discharge)
+    ///
+    /// Energy put into the battery counts as delivered, otherwise every charging
+    /// window would read as a loss. Returns `None` when nothing flowed in.
+    pub fn efficiency_percent(&self) -> Option<f32> {
+        let energy_in = self.production + self.grid_buy + self.battery_discharge;
+        if energy_in == 0 {
+            return None;
+        }
+
+        let energy_out = self.consumption + self.grid_sell + self.battery_loaded;
+        let percent = energy_out as f32 / energy_in as f32 * 100.0;
+
+        Some(percent.clamp(0.0, 100.0))
+    }
+}
+
+/// Keeps the energy counters of the last `window_minutes` and computes the
+/// system efficiency across that window.
+#[derive(Debug, Clone)]
+pub struct EfficiencyTracker {
+    config: EfficiencyConfig,
+    samples: VecDeque<(DateTime<Utc>, DataHistory)>,
+}
+
+impl EfficiencyTracker {
+    pub fn new(config: EfficiencyConfig) -> Self {
+        Self {
+            config,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn push(&mut self, timestamp: DateTime<Utc>, history: &DataHistory) -> Option<f32> {
+        self.samples.push_back((timestamp, history.clone()));
+
+        let window_start = timestamp - Duration::minutes(self.config.window_minutes);
+        while self
+            .samples
+            .front()
+            .is_some_and(|(sample_time, _)| *sample_time < window_start)
+        {
+            self.samples.pop_front();
+        }
+
+        let (_, oldest) = self.samples.front()?;
+        let (_, newest) = self.samples.back()?;
+
+        EnergyDelta::between(oldest, newest).efficiency_percent()
+    }
+
+    pub fn payload(&self, efficiency: f32) -> serde_json::Value {
+        json!({
+            "system_efficiency": (efficiency * 10.0).round() / 10.0,
+            "window_minutes": self.config.window_minutes,
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })
+    }
+}
+
+#[test]
+fn test_efficiency_over_window() {
+    let mut tracker = EfficiencyTracker::new(EfficiencyConfig {
+        enabled: true,
+        window_minutes: 60,
+    });
+
+    let start = Utc::now();
+    let first = DataHistory {
+        grid_buy: 1000,
+        grid_sell: 2000,
+        production_energy: 10000,
+        consumption_energy: 5000,
+        battery_loaded: 3000,
+        battery_discharge: 1000,
+        battery_cycles: 0,
+        self_consumed_energy: 8000,
+        counter_reset: false,
+    };
+
+    // A single sample has no delta yet
+    assert_eq!(tracker.push(start, &first), None);
+
+    // In: 800 PV + 100 grid + 100 battery = 1000 Wh
+    // Out: 600 loads + 200 export + 100 battery = 900 Wh
+    let second = DataHistory {
+        grid_buy: 1100,
+        grid_sell: 2200,
+        production_energy: 10800,
+        consumption_energy: 5600,
+        battery_loaded: 3100,
+        battery_discharge: 1100,
+        battery_cycles: 0,
+        self_consumed_energy: 8600,
+        counter_reset: false,
+    };
+
+    let efficiency = tracker
+        .push(start + Duration::minutes(30), &second)
+        .unwrap();
+    assert!((efficiency - 90.0).abs() < 0.01);
+
+    // Samples older than the window are dropped, leaving nothing to compare
+    let third = second.clone();
+    assert_eq!(tracker.push(start + Duration::minutes(120), &third), None);
+}
+
+#[test]
+fn test_efficiency_zero_denominator_and_clamp() {
+    assert_eq!(EnergyDelta::default().efficiency_percent(), None);
+
+    let delta = EnergyDelta {
+        production: 100,
+        consumption: 500,
+        ..Default::default()
+    };
+    assert_eq!(delta.efficiency_percent(), Some(100.0));
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+/// Stretch of cycles without any reading. Starts at the first failed
+/// collection and ends with the reading that closed the gap, so consumers
+/// know not to interpolate across it.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "event", rename = "data_gap")]
+pub struct DataGap {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub duration_secs: i64,
+    pub missed_cycles: u32,
+}
+
+impl DataGap {
+    pub fn payload(&self) -> Value {
+        let mut payload = json!(self);
+        payload["timestamp"] = json!(Utc::now().to_rfc3339());
+        payload
+    }
+}
+
+/// Counts failed collections in a row. Once collection works again after at
+/// least `min_cycles` failures the gap is reported, shorter hiccups only
+/// reset the counter. `min_cycles = 0` never reports a gap.
+#[derive(Debug, Clone)]
+pub struct GapDetector {
+    min_cycles: u32,
+    consecutive_failures: u32,
+    first_failure: Option<DateTime<Utc>>,
+}
+
+impl GapDetector {
+    pub fn new(min_cycles: u32) -> Self {
+        Self {
+            min_cycles,
+            consecutive_failures: 0,
+            first_failure: None,
+        }
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    pub fn record_failure(&mut self, now: DateTime<Utc>) {
+        self.first_failure.get_or_insert(now);
+        self.consecutive_failures += 1;
+    }
+
+    pub fn record_success(&mut self, now: DateTime<Utc>) -> Option<DataGap> {
+        let started_at = self.first_failure.take()?;
+        let missed_cycles = std::mem::take(&mut self.consecutive_failures);
+        if self.min_cycles == 0 || missed_cycles < self.min_cycles {
+            return None;
+        }
+
+        Some(DataGap {
+            started_at,
+            ended_at: now,
+            duration_secs: (now - started_at).num_seconds(),
+            missed_cycles,
+        })
+    }
+}
+
+#[test]
+fn test_data_gap_after_failed_cycles() {
+    let mut detector = GapDetector::new(3);
+    let start = Utc::now();
+    let at = |secs: i64| start + chrono::Duration::seconds(secs);
+
+    assert_eq!(detector.record_success(at(0)), None);
+
+    // Two failures stay below the threshold
+    detector.record_failure(at(60));
+    detector.record_failure(at(120));
+    assert_eq!(detector.record_success(at(180)), None);
+    assert_eq!(detector.consecutive_failures(), 0);
+
+    for cycle in 1..=5 {
+        detector.record_failure(at(180 + cycle * 60));
+    }
+    assert_eq!(detector.consecutive_failures(), 5);
+    let gap = detector.record_success(at(540)).unwrap();
+    assert_eq!(
+        gap,
+        DataGap {
+            started_at: at(240),
+            ended_at: at(540),
+            duration_secs: 300,
+            missed_cycles: 5,
+        }
+    );
+    assert_eq!(detector.record_success(at(600)), None);
+
+    let payload = gap.payload();
+    assert_eq!(payload["event"], "data_gap");
+    assert_eq!(payload["missed_cycles"], 5);
+
+    let mut disabled = GapDetector::new(0);
+    for cycle in 0..10 {
+        disabled.record_failure(at(cycle * 60));
+    }
+    assert_eq!(disabled.record_success(at(600)), None);
+}
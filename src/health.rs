@@ -1,10 +1,30 @@
+use crate::admin::{self, AdminCommand};
 use crate::calculator::{DataHistory, ProcessedData};
-use crate::collector::RawPVData;
-use crate::config::Config;
-use crate::db::{PostgresDatabase, SqliteCache};
-use crate::mqtt::{MQTTHealthStatus, SolarMqttClient};
+use crate::changes::ChangeDetector;
+use crate::collector::{HttpSelfHeal, InverterAuthError, RawEnergyData, RawPVData};
+use crate::config::{Config, DiscoveryMode, ProductionSource, StorageBackend};
+use crate::daily::DailyTotals;
+use crate::db::{PostgresDatabase, PostgresHealth, SqliteCache, SyncResult};
+use crate::efficiency::EfficiencyTracker;
+use crate::gap::GapDetector;
+use crate::latency::LatencyHistogram;
+use crate::meter::ExternalMeter;
+use crate::metrics::Metrics;
+use crate::mqtt::{
+    DiagnosticsSnapshot, DiscoveryComponent, MQTTHealthStatus, SYNC_PAYLOAD, SolarMqttClient,
+};
+use crate::notify::{Notifier, TransitionEvent, WebhookNotifier};
+use crate::outage::{GridEvent, OutageDetector};
+use crate::server::{self, AppState, DeviceState, LiveFeed, SharedStatus};
+use crate::sink::{MetricSink, NullSink};
+use crate::smoothing::PowerSmoother;
+use crate::snapshot::Snapshot;
+use crate::stale::StaleDetector;
+use crate::state_time::StateTimeTracker;
+use crate::tariff::TariffTracker;
 use color_eyre::eyre::{Result, WrapErr, eyre};
 use statum::{machine, state};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
@@ -33,6 +53,61 @@ pub enum CoordinatorResult {
     Shutdown,
 }
 
+/// Why a cycle did less than a full collect/store/publish.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkipReason {
+    Maintenance,
+    CollectFailed,
+    Deduped,
+    Throttled,
+    DryRun,
+    Implausible,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SkipReason::Maintenance => "Maintenance",
+            SkipReason::CollectFailed => "CollectFailed",
+            SkipReason::Deduped => "Deduped",
+            SkipReason::Throttled => "Throttled",
+            SkipReason::DryRun => "DryRun",
+            SkipReason::Implausible => "Implausible",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Which subsystems were up once `start_with` returned. A subsystem still
+/// pending at `startup_timeout_secs` counts as down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StartupReport {
+    pub db_ready: bool,
+    pub mqtt_ready: bool,
+}
+
+/// Runs one startup step until the shared startup deadline. `None` means the
+/// subsystem was still pending when the deadline passed.
+async fn startup_step<T>(
+    deadline: tokio::time::Instant,
+    subsystem: &str,
+    step: impl Future<Output = Result<T>>,
+) -> Result<Option<T>> {
+    match tokio::time::timeout_at(deadline, step).await {
+        Ok(result) => result.map(Some),
+        Err(_) => {
+            error!(subsystem, "Startup timed out, subsystem still pending");
+            Ok(None)
+        }
+    }
+}
+
+/// One structured line per skipped or partial cycle, so the reason can be
+/// filtered on in the logs.
+pub fn log_cycle_skipped(state: &str, reason: SkipReason, detail: &str) {
+    info!(skip_reason = %reason, state, detail, "Cycle skipped");
+}
+
 #[derive(Debug)]
 pub enum HealthStateTransition {
     ToHealthy,
@@ -42,6 +117,36 @@ pub enum HealthStateTransition {
     ToShutdown,
 }
 
+impl HealthStateTransition {
+    /// Which subsystem failed or came back, for the transition log. The
+    /// transitions only say where to go, so the reason follows from the
+    /// state they leave.
+    pub fn reason(&self, from: &CoordinatorKind) -> &'static str {
+        match (self, from) {
+            (HealthStateTransition::ToHealthy, CoordinatorKind::DegradedNoDB(_)) => {
+                "PostgreSQL recovered"
+            }
+            (HealthStateTransition::ToHealthy, CoordinatorKind::DegradedNoMqtt(_)) => {
+                "MQTT recovered"
+            }
+            (HealthStateTransition::ToHealthy, _) => "PostgreSQL and MQTT recovered",
+            (HealthStateTransition::ToDegradedNoDB(..), _) => "PostgreSQL write failed",
+            (HealthStateTransition::ToDegradedNoMqtt, CoordinatorKind::CacheOnly(_)) => {
+                "PostgreSQL recovered, MQTT still down"
+            }
+            (HealthStateTransition::ToDegradedNoMqtt, _) => "MQTT publish failed",
+            (HealthStateTransition::ToCacheOnly(..), CoordinatorKind::DegradedNoDB(_)) => {
+                "MQTT publish failed"
+            }
+            (HealthStateTransition::ToCacheOnly(..), CoordinatorKind::DegradedNoMqtt(_)) => {
+                "PostgreSQL write failed"
+            }
+            (HealthStateTransition::ToCacheOnly(..), _) => "PostgreSQL and MQTT failed",
+            (HealthStateTransition::ToShutdown, _) => "SQLite cache write failed",
+        }
+    }
+}
+
 // =============================================================================
 // COORDINATOR STATE MACHINE
 // =============================================================================
@@ -49,11 +154,73 @@ pub enum HealthStateTransition {
 #[machine]
 #[derive(Clone, Debug)]
 pub struct Coordinator<S: HealthState> {
-    mqtt_client: SolarMqttClient,
-    pgdb: PostgresDatabase,
-    cache: SqliteCache,
     config: Config,
-    last_recovery_attempt: Instant,
+    storage: Storage,
+    publish: PublishState,
+    detectors: Detectors,
+    trackers: Trackers,
+    collection: Collection,
+    recovery: Recovery,
+}
+
+#[derive(Clone, Debug)]
+struct Storage {
+    /// Both None with `storage_backend = "none"`
+    pgdb: Option<PostgresDatabase>,
+    cache: Option<SqliteCache>,
+    /// Where every reading is written, the same Postgres as `pgdb` or a
+    /// `NullSink` without storage backend
+    sink: Arc<dyn MetricSink>,
+    last_prune: Option<Instant>,
+    last_archive_cleanup: Option<Instant>,
+}
+
+#[derive(Clone, Debug)]
+struct PublishState {
+    client: SolarMqttClient,
+    /// Reading the last power publish was compared against, see
+    /// `publish_changed_only`
+    last_power: Option<ProcessedData>,
+    partial_power_publishes: u32,
+    /// Moving average for the MQTT power values, see `smoothing_alpha`
+    power_smoother: PowerSmoother,
+    live_feed: LiveFeed,
+    /// Told about every state transition, None without a webhook
+    notifier: Option<Arc<dyn Notifier>>,
+}
+
+#[derive(Clone, Debug)]
+struct Detectors {
+    outage: OutageDetector,
+    /// Counts the `consecutive_collection_failures` behind a data gap
+    gap: GapDetector,
+    stale: StaleDetector,
+    change: ChangeDetector,
+}
+
+#[derive(Clone, Debug)]
+struct Trackers {
+    efficiency: EfficiencyTracker,
+    tariff: TariffTracker,
+    daily_totals: DailyTotals,
+    metrics: Metrics,
+}
+
+/// What the inverter readings leave behind between cycles
+#[derive(Clone, Debug)]
+struct Collection {
+    latency: LatencyHistogram,
+    http_self_heal: HttpSelfHeal,
+    external_meter: Option<ExternalMeter>,
+    last_energy: Option<RawEnergyData>,
+    latest_snapshot: Option<Snapshot>,
+    restored_snapshot: Option<Snapshot>,
+}
+
+#[derive(Clone, Debug)]
+struct Recovery {
+    last_attempt: Instant,
+    backoff_attempts: u32,
 }
 
 // =============================================================================
@@ -62,30 +229,269 @@ pub struct Coordinator<S: HealthState> {
 
 impl Coordinator<Healthy> {
     pub async fn start() -> Result<Self> {
-        let config = Config::new();
-        let client = SolarMqttClient::new(&config.mqtt_config, "pv_api".to_string()).await?;
-        let db = PostgresDatabase::new(config.database_config.clone()).await?;
-        let cache = SqliteCache::new(config.sqlite_cache_config.clone()).await?;
-        client.setup_discovery().await?;
+        Self::start_with(Config::new()?).await
+    }
 
-        client.publish_availability(true).await;
-        Ok(Coordinator::new(client, db, cache, config, Instant::now()))
+    pub async fn start_with(config: Config) -> Result<Self> {
+        Ok(Self::start_reporting(config).await?.0)
+    }
+
+    /// Database and MQTT setup run side by side, both bounded by
+    /// `startup_timeout_secs`. Whatever is still pending then is reported
+    /// as down instead of holding up startup.
+    pub async fn start_reporting(config: Config) -> Result<(Self, StartupReport)> {
+        config.validate()?;
+        if config.dry_run {
+            warn!("Dry run: nothing is written to PostgreSQL, the cache or MQTT");
+        }
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_secs(config.startup_timeout_secs);
+        let client = SolarMqttClient::new(&config.mqtt_config, config.device_id.clone())
+            .await?
+            .with_dry_run(config.dry_run);
+
+        // Failed discovery configs do not abort the start, the coordinator
+        // comes up without MQTT and sends them again on recovery
+        let mqtt_setup = startup_step(deadline, "MQTT", async {
+            let discovered = match setup_discovery(&client, &config).await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!(error = %e, "Discovery incomplete, starting without MQTT");
+                    false
+                }
+            };
+
+            if config.mqtt_config.admin_token.is_some() {
+                client.subscribe_to_commands().await?;
+            }
+
+            client.subscribe_to_hass_status().await?;
+            client.subscribe_to_refresh().await?;
+            client.publish_availability(true).await;
+            Ok(discovered)
+        });
+        let postgres = config.storage_backend == StorageBackend::Postgres;
+        let db_setup = async {
+            if !postgres {
+                return Ok(None);
+            }
+            startup_step(
+                deadline,
+                "PostgreSQL",
+                PostgresDatabase::new(config.database_config.clone()),
+            )
+            .await
+        };
+        let cache_setup = async {
+            if !postgres {
+                return Ok(None);
+            }
+            startup_step(
+                deadline,
+                "SQLite cache",
+                SqliteCache::new(config.sqlite_cache_config.clone()),
+            )
+            .await?
+            .map(Some)
+            .ok_or_else(|| eyre!("Startup timed out opening the SQLite cache"))
+        };
+        let (mqtt_setup, db_setup, cache_setup) = tokio::join!(mqtt_setup, db_setup, cache_setup);
+        let mqtt_ready = mqtt_setup? == Some(true);
+        let metrics = Metrics::for_device(&config.device_id);
+        let cache = cache_setup?.map(|cache| {
+            cache
+                .with_dry_run(config.dry_run)
+                .with_metrics(metrics.clone())
+        });
+        let db = match db_setup? {
+            Some(db) => Some(db),
+            None if postgres => Some(PostgresDatabase::connect_later(
+                config.database_config.clone(),
+            )),
+            None => None,
+        }
+        .map(|db| {
+            db.with_dry_run(config.dry_run)
+                .with_metrics(metrics.clone())
+        });
+        let sink: Arc<dyn MetricSink> = match &db {
+            Some(db) => Arc::new(db.clone()),
+            None => {
+                info!("No storage backend configured, readings are only published to MQTT");
+                Arc::new(NullSink)
+            }
+        };
+        let db_ready = match &db {
+            Some(db) => db.get_health().await == PostgresHealth::Healthy,
+            None => true,
+        };
+
+        let change_detector = ChangeDetector::new(config.change_event_config.clone());
+        let efficiency_tracker = EfficiencyTracker::new(config.efficiency_config.clone());
+        let tariff_tracker = TariffTracker::new(config.tariff_config.clone())?;
+        let daily_totals = DailyTotals::load(config.daily_totals_config.clone(), config.tz()).await;
+        let http_self_heal = HttpSelfHeal::new(config.http_rebuild_after_failures);
+        let outage_detector = OutageDetector::new(config.grid_outage_config.clone());
+        let gap_detector = GapDetector::new(config.data_gap_min_cycles);
+        let stale_detector = StaleDetector::new(config.stale_data_cycles);
+        let external_meter = ExternalMeter::new(&config.external_meter_config)?;
+        let notifier = WebhookNotifier::new(&config.notify_config)?
+            .map(|notifier| Arc::new(notifier) as Arc<dyn Notifier>);
+        let power_smoother = PowerSmoother::new(
+            config.mqtt_config.smoothing_alpha,
+            config.battery_config.empty_threshold,
+            config.battery_config.full_threshold,
+        );
+        let restored_snapshot = match Snapshot::load(&config.snapshot_path).await {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                warn!("Could not restore last snapshot: {}", e);
+                None
+            }
+        };
+        let coordinator = Coordinator::new(
+            config,
+            Storage {
+                pgdb: db,
+                cache,
+                sink,
+                last_prune: None,
+                last_archive_cleanup: None,
+            },
+            PublishState {
+                client,
+                last_power: None,
+                partial_power_publishes: 0,
+                power_smoother,
+                live_feed: LiveFeed::default(),
+                notifier,
+            },
+            Detectors {
+                outage: outage_detector,
+                gap: gap_detector,
+                stale: stale_detector,
+                change: change_detector,
+            },
+            Trackers {
+                efficiency: efficiency_tracker,
+                tariff: tariff_tracker,
+                daily_totals,
+                metrics,
+            },
+            Collection {
+                latency: LatencyHistogram::default(),
+                http_self_heal,
+                external_meter,
+                last_energy: None,
+                latest_snapshot: None,
+                restored_snapshot,
+            },
+            Recovery {
+                last_attempt: Instant::now(),
+                backoff_attempts: 0,
+            },
+        );
+        Ok((
+            coordinator,
+            StartupReport {
+                db_ready,
+                mqtt_ready,
+            },
+        ))
+    }
+
+    /// Starting state for the subsystems in `report`. Nothing has been
+    /// collected yet, so unlike the regular transitions there is no reading
+    /// to back up to the cache.
+    pub fn into_startup_state(self, report: StartupReport) -> CoordinatorKind {
+        match (report.db_ready, report.mqtt_ready) {
+            (true, true) => CoordinatorKind::Healthy(self),
+            (false, true) => {
+                warn!("PostgreSQL unavailable at startup, starting in DegradedNoDB");
+                CoordinatorKind::DegradedNoDB(self.transition())
+            }
+            (true, false) => {
+                warn!("MQTT unavailable at startup, starting in DegradedNoMqtt");
+                CoordinatorKind::DegradedNoMqtt(self.transition())
+            }
+            (false, false) => {
+                error!("PostgreSQL and MQTT unavailable at startup, starting in CacheOnly");
+                CoordinatorKind::CacheOnly(self.transition())
+            }
+        }
+    }
+
+    /// Writes the readings to `sink` instead of Postgres.
+    pub fn with_sink(mut self, sink: Arc<dyn MetricSink>) -> Self {
+        self.storage.sink = sink;
+        self
+    }
+
+    /// Sends the state transitions to `notifier` instead of the configured
+    /// webhook.
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.publish.notifier = Some(notifier);
+        self
     }
 
     pub async fn run_cycle(&mut self) -> Result<CoordinatorResult> {
         info!("Running standard cycle in Healthy state");
 
-        let raw_data = collect_raw_data_with_retry(&self.config.pv_baseaddress).await?;
-        let processed_data =
-            ProcessedData::process_raw(raw_data.clone(), &self.config.battery_config);
-        let data_history = DataHistory::process_raw(raw_data, &self.config.battery_config);
-
-        let db_result = self.pgdb.store_power_data(&processed_data).await;
-        let energy_result = self.pgdb.store_energy_data(&data_history).await;
-        let mqtt_result = self.mqtt_client.publish_current_data(&processed_data).await;
+        // The restored snapshot only covers the very first collection
+        let restored_snapshot = self.collection.restored_snapshot.take();
+        let raw_data = match self.collect_raw_data().await {
+            Ok(raw_data) => raw_data,
+            Err(e) => match restored_snapshot {
+                Some(snapshot) => {
+                    warn!(
+                        "First collection failed, publishing restored snapshot: {}",
+                        e
+                    );
+                    self.log_cycle_skipped("Healthy", SkipReason::CollectFailed, &e.to_string());
+                    self.publish.client.publish_stale_snapshot(&snapshot).await;
+                    return Ok(CoordinatorResult::Continue);
+                }
+                None => return Err(e),
+            },
+        };
+        if !self.is_plausible(&raw_data, "Healthy") {
+            return Ok(CoordinatorResult::Continue);
+        }
+        let processed_data = self.process_power(raw_data.clone()).await;
+        let data_history = self.process_history(raw_data);
+        self.save_snapshot(&processed_data, &data_history).await;
+        self.trackers.metrics.observe(&processed_data);
+        self.track_grid_outage(&processed_data).await;
+
+        let mirrored = self.mirror_to_cache(&processed_data, &data_history).await;
+        let db_result = self.storage.sink.store_power(&processed_data).await;
+        let energy_result = self.storage.sink.store_energy(&data_history).await;
+        self.release_mirrored(mirrored).await;
+        let mqtt_result = self.publish_power(&processed_data).await;
+        if mqtt_result.is_err() {
+            self.trackers.metrics.record_mqtt_publish_failure();
+        }
 
-        self.mqtt_client.publish_state_data(&processed_data).await;
-        self.mqtt_client.publish_history_data(&data_history).await;
+        self.publish
+            .client
+            .publish_state_data(&processed_data)
+            .await;
+        self.publish
+            .client
+            .publish_history_data(&data_history)
+            .await;
+        if mqtt_result.is_ok() {
+            self.publish_change_events(&processed_data).await;
+            self.publish_efficiency(&data_history).await;
+        }
+        self.record_tariff(&data_history, energy_result.is_ok(), mqtt_result.is_ok())
+            .await;
+        self.record_daily_totals(&data_history, mqtt_result.is_ok())
+            .await;
+        if db_result.is_ok() && energy_result.is_ok() {
+            self.prune_if_due().await;
+        }
+        self.cleanup_archive_if_due().await;
         // Determine transition based on what failed - pass data to transitions
         match (
             db_result.is_ok() && energy_result.is_ok(),
@@ -116,6 +522,37 @@ impl Coordinator<Healthy> {
         }
     }
 
+    /// One collect/process/store/publish pass without entering the main loop.
+    /// Data that could not reach Postgres is kept in the cache for the next run.
+    pub async fn run_single_cycle(&mut self) -> Result<()> {
+        info!("Running single collection cycle");
+
+        let result = match self.run_cycle().await? {
+            CoordinatorResult::Continue => Ok(()),
+            CoordinatorResult::TransitionTo(HealthStateTransition::ToDegradedNoDB(
+                power_data,
+                energy_data,
+            ))
+            | CoordinatorResult::TransitionTo(HealthStateTransition::ToCacheOnly(
+                power_data,
+                energy_data,
+            )) => {
+                self.store_to_cache(&power_data, &energy_data)
+                    .await
+                    .wrap_err("Failed to cache data after database failure")?;
+                Err(eyre!("Database unavailable, data was cached instead"))
+            }
+            CoordinatorResult::TransitionTo(transition) => Err(eyre!(
+                "Single cycle did not complete cleanly: {:?}",
+                transition
+            )),
+            CoordinatorResult::Shutdown => Err(eyre!("Shutdown requested during single cycle")),
+        };
+
+        self.publish.client.publish_availability(false).await;
+        result
+    }
+
     pub async fn to_degraded_no_db(
         self,
         power_data: ProcessedData,
@@ -123,11 +560,11 @@ impl Coordinator<Healthy> {
     ) -> Coordinator<DegradedNoDB> {
         info!("Transitioning from Healthy to DegradedNoDB - saving data to cache");
 
-        let power_res = self.cache.store_power_data(&power_data).await;
-        let energy_res = self.cache.store_energy_data(&energy_data).await;
-
-        if power_res.is_err() || energy_res.is_err() {
-            error!("CRITICAL: Cache storage failed during transition - forcing shutdown");
+        if let Err(e) = self.store_to_cache(&power_data, &energy_data).await {
+            error!(
+                "CRITICAL: Cache storage failed during transition - forcing shutdown: {:?}",
+                e
+            );
             self.to_shutdown();
             unreachable!();
         } else {
@@ -148,11 +585,11 @@ impl Coordinator<Healthy> {
     ) -> Coordinator<CacheOnly> {
         info!("Transitioning from Healthy to CacheOnly - saving data to cache");
 
-        let power_res = self.cache.store_power_data(&power_data).await;
-        let energy_res = self.cache.store_energy_data(&energy_data).await;
-
-        if power_res.is_err() || energy_res.is_err() {
-            error!("CRITICAL: Cache storage failed during transition - forcing shutdown");
+        if let Err(e) = self.store_to_cache(&power_data, &energy_data).await {
+            error!(
+                "CRITICAL: Cache storage failed during transition - forcing shutdown: {:?}",
+                e
+            );
             self.to_shutdown();
             unreachable!();
         } else {
@@ -171,9 +608,10 @@ impl Coordinator<DegradedNoDB> {
     pub async fn run_cycle(&mut self) -> Result<CoordinatorResult> {
         if self.should_attempt_recovery() {
             debug!("Attempting database recovery in DegradedNoDB");
-            match self.pgdb.health_check().await {
-                Ok(crate::db::PostgresHealth::Healthy) => {
+            match self.storage.sink.health_check().await {
+                Ok(true) => {
                     info!("Database recovered! Transitioning to Healthy and syncing cache");
+                    self.reset_recovery_backoff();
                     // Trigger cache sync during transition
                     return Ok(CoordinatorResult::TransitionTo(
                         HealthStateTransition::ToHealthy,
@@ -181,7 +619,7 @@ impl Coordinator<DegradedNoDB> {
                 }
                 _ => {
                     debug!("Database still not available");
-                    self.last_recovery_attempt = Instant::now();
+                    self.record_failed_recovery();
                 }
             }
         }
@@ -189,27 +627,31 @@ impl Coordinator<DegradedNoDB> {
         // Normal degraded cycle: collect -> process -> store cache + MQTT
         info!("Running degraded cycle (no DB) - using cache + MQTT");
 
-        let raw_data = collect_raw_data_with_retry(&self.config.pv_baseaddress).await?;
+        let raw_data = self.collect_raw_data().await?;
+        if !self.is_plausible(&raw_data, "DegradedNoDB") {
+            return Ok(CoordinatorResult::Continue);
+        }
 
-        let processed_data =
-            ProcessedData::process_raw(raw_data.clone(), &self.config.battery_config);
-        let data_history = DataHistory::process_raw(raw_data, &self.config.battery_config);
+        let processed_data = self.process_power(raw_data.clone()).await;
+        let data_history = self.process_history(raw_data);
+        self.save_snapshot(&processed_data, &data_history).await;
+        self.trackers.metrics.observe(&processed_data);
+        self.track_grid_outage(&processed_data).await;
 
-        if let Err(e) = self.cache.store_power_data(&processed_data).await {
-            error!("Cache storage failed: {}", e);
+        if let Err(e) = self.store_to_cache(&processed_data, &data_history).await {
+            error!("Cache storage failed: {:?}", e);
             return Ok(CoordinatorResult::TransitionTo(
                 HealthStateTransition::ToShutdown,
             ));
         }
 
-        if let Err(e) = self.cache.store_energy_data(&data_history).await {
-            error!("Cache energy storage failed: {}", e);
-            return Ok(CoordinatorResult::TransitionTo(
-                HealthStateTransition::ToShutdown,
-            ));
-        }
+        self.enforce_cache_limit().await;
+        self.cleanup_archive_if_due().await;
 
-        if let Err(e) = self.mqtt_client.publish_current_data(&processed_data).await {
+        if let Err(e) = self.publish_power(&processed_data).await {
+            self.trackers.metrics.record_mqtt_publish_failure();
+            self.record_tariff(&data_history, false, false).await;
+            self.record_daily_totals(&data_history, false).await;
             warn!(
                 "MQTT failed in DegradedNoDB: {}, transitioning to CacheOnly",
                 e
@@ -219,8 +661,18 @@ impl Coordinator<DegradedNoDB> {
             ));
         }
 
-        self.mqtt_client.publish_state_data(&processed_data).await;
-        self.mqtt_client.publish_history_data(&data_history).await;
+        self.publish
+            .client
+            .publish_state_data(&processed_data)
+            .await;
+        self.publish
+            .client
+            .publish_history_data(&data_history)
+            .await;
+        self.publish_change_events(&processed_data).await;
+        self.publish_efficiency(&data_history).await;
+        self.record_tariff(&data_history, false, true).await;
+        self.record_daily_totals(&data_history, true).await;
         debug!("DegradedNoDB cycle completed successfully");
         Ok(CoordinatorResult::Continue)
     }
@@ -229,7 +681,7 @@ impl Coordinator<DegradedNoDB> {
         info!("Transitioning from DegradedNoDB to Healthy - starting cache sync");
 
         // Sync cache to postgres during transition
-        if let Err(e) = self.cache.sync_to_postgres(&self.pgdb).await {
+        if let Err(e) = self.sync_cache().await {
             warn!("Cache sync failed during transition: {}", e);
         } else {
             info!("Cache sync completed successfully");
@@ -254,31 +706,41 @@ impl Coordinator<DegradedNoMqtt> {
         // First: Try to recover MQTT connection
         if self.should_attempt_recovery() {
             debug!("Attempting MQTT recovery in DegradedNoMqtt");
-            match self.mqtt_client.get_health_status().await {
+            match self.publish.client.get_health_status().await {
                 MQTTHealthStatus::Healthy => {
                     info!("MQTT recovered! Transitioning to Healthy");
+                    self.reset_recovery_backoff();
                     return Ok(CoordinatorResult::TransitionTo(
                         HealthStateTransition::ToHealthy,
                     ));
                 }
                 _ => {
                     debug!("MQTT still not available");
-                    self.last_recovery_attempt = Instant::now();
+                    self.record_failed_recovery();
                 }
             }
         }
 
         info!("Running degraded cycle (no MQTT) - using DB only");
 
-        let raw_data = collect_raw_data_with_retry(&self.config.pv_baseaddress).await?;
+        let raw_data = self.collect_raw_data().await?;
+        if !self.is_plausible(&raw_data, "DegradedNoMqtt") {
+            return Ok(CoordinatorResult::Continue);
+        }
 
-        let processed_data =
-            ProcessedData::process_raw(raw_data.clone(), &self.config.battery_config);
-        let data_history = DataHistory::process_raw(raw_data, &self.config.battery_config);
+        let processed_data = self.process_power(raw_data.clone()).await;
+        let data_history = self.process_history(raw_data);
+        self.save_snapshot(&processed_data, &data_history).await;
+        self.trackers.metrics.observe(&processed_data);
+        self.track_grid_outage(&processed_data).await;
 
         // Store to DB
-        let db_result = self.pgdb.store_power_data(&processed_data).await;
-        let energy_result = self.pgdb.store_energy_data(&data_history).await;
+        let db_result = self.storage.sink.store_power(&processed_data).await;
+        let energy_result = self.storage.sink.store_energy(&data_history).await;
+
+        self.record_tariff(&data_history, energy_result.is_ok(), false)
+            .await;
+        self.record_daily_totals(&data_history, false).await;
 
         if db_result.is_err() || energy_result.is_err() {
             warn!("Database failed in DegradedNoMqtt, transitioning to CacheOnly");
@@ -287,6 +749,8 @@ impl Coordinator<DegradedNoMqtt> {
             ));
         }
 
+        self.prune_if_due().await;
+        self.cleanup_archive_if_due().await;
         debug!("DegradedNoMqtt cycle completed successfully");
         Ok(CoordinatorResult::Continue)
     }
@@ -312,34 +776,31 @@ impl Coordinator<CacheOnly> {
         if self.should_attempt_recovery() {
             debug!("Attempting service recovery in CacheOnly");
 
-            let db_healthy = matches!(
-                self.pgdb
-                    .health_check()
-                    .await
-                    .unwrap_or(crate::db::PostgresHealth::Disconnected),
-                crate::db::PostgresHealth::Healthy
-            );
+            let db_healthy = self.storage.sink.health_check().await.unwrap_or(false);
 
             let mqtt_healthy = matches!(
-                self.mqtt_client.get_health_status().await,
+                self.publish.client.get_health_status().await,
                 MQTTHealthStatus::Healthy
             );
 
             match (db_healthy, mqtt_healthy) {
                 (true, true) => {
                     info!("Both services recovered! Transitioning to Healthy");
+                    self.reset_recovery_backoff();
                     return Ok(CoordinatorResult::TransitionTo(
                         HealthStateTransition::ToHealthy,
                     ));
                 }
                 (true, false) => {
                     info!("Database recovered, transitioning to DegradedNoMqtt");
+                    self.reset_recovery_backoff();
                     return Ok(CoordinatorResult::TransitionTo(
                         HealthStateTransition::ToDegradedNoMqtt,
                     ));
                 }
                 (false, true) => {
                     info!("MQTT recovered, transitioning to DegradedNoDB");
+                    self.reset_recovery_backoff();
                     // Don't pass default data, just transition
                     return Ok(CoordinatorResult::TransitionTo(
                         HealthStateTransition::ToDegradedNoMqtt,
@@ -347,7 +808,7 @@ impl Coordinator<CacheOnly> {
                 }
                 (false, false) => {
                     debug!("No services recovered yet");
-                    self.last_recovery_attempt = Instant::now();
+                    self.record_failed_recovery();
                 }
             }
         }
@@ -355,28 +816,46 @@ impl Coordinator<CacheOnly> {
         // Normal cache-only cycle: try to collect -> store to cache only
         info!("Running cache-only cycle");
 
-        if let Ok(raw_data) = RawPVData::fill_raw(&self.config.pv_baseaddress).await {
-            let processed_data =
-                ProcessedData::process_raw(raw_data.clone(), &self.config.battery_config);
-            let data_history = DataHistory::process_raw(raw_data, &self.config.battery_config);
-
-            if let Err(e) = self.cache.store_power_data(&processed_data).await {
-                error!("Cache storage failed in CacheOnly: {}", e);
-                return Ok(CoordinatorResult::TransitionTo(
-                    HealthStateTransition::ToShutdown,
-                ));
+        let started = Instant::now();
+        let result = RawPVData::fill_raw(
+            &self.config.pv_baseaddress,
+            &self.config.collected_channels(),
+            &self.config.pv_auth,
+        )
+        .await;
+        self.collection.latency.record(started.elapsed());
+
+        if let Ok(raw_data) = result {
+            self.collection.http_self_heal.record_success();
+            self.report_data_gap().await;
+            self.check_stale(&raw_data);
+            if !self.is_plausible(&raw_data, "CacheOnly") {
+                return Ok(CoordinatorResult::Continue);
             }
-
-            if let Err(e) = self.cache.store_energy_data(&data_history).await {
-                error!("Cache energy storage failed in CacheOnly: {}", e);
+            let processed_data = self.process_power(raw_data.clone()).await;
+            let data_history = self.process_history(raw_data);
+            self.save_snapshot(&processed_data, &data_history).await;
+            self.trackers.metrics.observe(&processed_data);
+            self.track_grid_outage(&processed_data).await;
+
+            if let Err(e) = self.store_to_cache(&processed_data, &data_history).await {
+                error!("Cache storage failed in CacheOnly: {:?}", e);
                 return Ok(CoordinatorResult::TransitionTo(
                     HealthStateTransition::ToShutdown,
                 ));
             }
 
+            self.enforce_cache_limit().await;
+            self.cleanup_archive_if_due().await;
+            self.record_tariff(&data_history, false, false).await;
+            self.record_daily_totals(&data_history, false).await;
             debug!("Data stored to cache successfully");
         } else {
+            self.trackers.metrics.record_collection_failure();
+            self.collection.http_self_heal.record_failure();
+            self.detectors.gap.record_failure(chrono::Utc::now());
             warn!("Data collection failed in CacheOnly mode");
+            self.log_cycle_skipped("CacheOnly", SkipReason::CollectFailed, "collection failed");
         }
 
         Ok(CoordinatorResult::Continue)
@@ -386,7 +865,7 @@ impl Coordinator<CacheOnly> {
         info!("Transitioning from CacheOnly to Healthy - starting cache sync");
 
         // Sync cache to postgres during transition
-        if let Err(e) = self.cache.sync_to_postgres(&self.pgdb).await {
+        if let Err(e) = self.sync_cache().await {
             warn!("Cache sync failed during transition: {}", e);
         } else {
             info!("Cache sync completed successfully");
@@ -421,11 +900,18 @@ impl Coordinator<Shutdown> {
     pub async fn cleanup(&self) -> Result<()> {
         info!("Performing cleanup operations");
 
+        if self.config.mqtt_config.publish_health_state {
+            self.publish
+                .client
+                .publish_health_state(HEALTH_STATE_OPTIONS[4])
+                .await;
+        }
+
         // Publish offline status
-        self.mqtt_client.publish_availability(false).await;
+        self.publish.client.publish_availability(false).await;
 
         // Sync any remaining cache data
-        if let Err(e) = self.cache.sync_to_postgres(&self.pgdb).await {
+        if let Err(e) = self.sync_cache().await {
             warn!("Failed to sync cache during shutdown: {}", e);
         }
 
@@ -440,19 +926,492 @@ impl Coordinator<Shutdown> {
 
 impl<S: HealthState> Coordinator<S> {
     fn should_attempt_recovery(&self) -> bool {
-        // Try recovery every 30 seconds
-        self.last_recovery_attempt.elapsed() > Duration::from_secs(10)
+        // Start at `recovery_attempt_interval_secs` and double after every failed attempt
+        self.recovery.last_attempt.elapsed()
+            > recovery_delay(
+                self.config.recovery_attempt_interval_secs,
+                self.recovery.backoff_attempts,
+            )
+    }
+
+    /// Applies `retention_days` once a day. A failed prune is retried on the
+    /// next day, it never fails the cycle.
+    async fn prune_if_due(&mut self) {
+        let retention_days = self.config.database_config.retention_days;
+        if retention_days == 0
+            || self
+                .storage
+                .last_prune
+                .is_some_and(|last| last.elapsed() < PRUNE_INTERVAL)
+        {
+            return;
+        }
+
+        let Some(pgdb) = &self.storage.pgdb else {
+            return;
+        };
+
+        self.storage.last_prune = Some(Instant::now());
+        if let Err(e) = pgdb
+            .prune_older_than(chrono::Duration::days(retention_days as i64))
+            .await
+        {
+            warn!("Pruning old PostgreSQL rows failed: {}", e);
+        }
+    }
+
+    /// Daily removal of archive rows past `cleanup_threshold_days`. Runs in
+    /// every state that writes to the cache, Postgres is not involved.
+    async fn cleanup_archive_if_due(&mut self) {
+        if self
+            .storage
+            .last_archive_cleanup
+            .is_some_and(|last| last.elapsed() < PRUNE_INTERVAL)
+        {
+            return;
+        }
+
+        let Some(cache) = &self.storage.cache else {
+            return;
+        };
+
+        self.storage.last_archive_cleanup = Some(Instant::now());
+        if let Err(e) = cache.cleanup_archive().await {
+            warn!("Cleaning up the cache archive failed: {}", e);
+        }
+    }
+
+    fn record_failed_recovery(&mut self) {
+        self.recovery.last_attempt = Instant::now();
+        self.recovery.backoff_attempts = self.recovery.backoff_attempts.saturating_add(1);
+        debug!(
+            attempts = self.recovery.backoff_attempts,
+            next_delay_secs = recovery_delay(
+                self.config.recovery_attempt_interval_secs,
+                self.recovery.backoff_attempts
+            )
+            .as_secs(),
+            "Recovery attempt failed, backing off"
+        );
+    }
+
+    fn reset_recovery_backoff(&mut self) {
+        self.recovery.backoff_attempts = 0;
+    }
+
+    fn log_cycle_skipped(&self, state: &str, reason: SkipReason, detail: &str) {
+        if self.config.log_skipped_cycles {
+            log_cycle_skipped(state, reason, detail);
+        }
+    }
+
+    async fn collect_raw_data(&mut self) -> Result<RawPVData> {
+        let result = collect_raw_data_with_retry(&self.config, &mut self.collection.latency).await;
+        match &result {
+            Ok(raw_data) => {
+                self.collection.http_self_heal.record_success();
+                self.report_data_gap().await;
+                self.check_stale(raw_data);
+            }
+            Err(_) => {
+                self.trackers.metrics.record_collection_failure();
+                self.collection.http_self_heal.record_failure();
+                self.detectors.gap.record_failure(chrono::Utc::now());
+            }
+        }
+        result
+    }
+
+    /// The full power payload, or with `publish_changed_only` just the fields
+    /// that moved since the previous reading. After a failed publish the
+    /// next one is full again. The values are smoothed first when
+    /// `smoothing_alpha` is below 1.
+    async fn publish_power(&mut self, data: &ProcessedData) -> Result<()> {
+        let data = &self.publish.power_smoother.apply(data);
+        let mqtt_config = &self.config.mqtt_config;
+        let result = match self.publish.last_power.take() {
+            Some(previous)
+                if mqtt_config.publish_changed_only
+                    && self.publish.partial_power_publishes + 1
+                        < mqtt_config.full_publish_every =>
+            {
+                self.publish.partial_power_publishes += 1;
+                self.publish.client.publish_changed(&previous, data).await
+            }
+            _ => {
+                self.publish.partial_power_publishes = 0;
+                self.publish.client.publish_current_data(data).await
+            }
+        };
+        if result.is_ok() {
+            self.publish.last_power = Some(data.clone());
+        }
+        result
+    }
+
+    /// Publishes the gap left by the failed collections before this one.
+    async fn report_data_gap(&mut self) {
+        let Some(gap) = self.detectors.gap.record_success(chrono::Utc::now()) else {
+            return;
+        };
+
+        warn!(
+            started_at = %gap.started_at,
+            ended_at = %gap.ended_at,
+            missed_cycles = gap.missed_cycles,
+            "Collection resumed after a data gap"
+        );
+        self.publish.client.publish_data_gap(&gap).await;
+    }
+
+    /// Warns once when the inverter starts repeating the same reading, the
+    /// flag itself goes out with the diagnostics.
+    fn check_stale(&mut self, raw_data: &RawPVData) {
+        if self.detectors.stale.observe(raw_data) {
+            warn!(
+                unchanged_cycles = self.detectors.stale.unchanged_cycles(),
+                "Inverter keeps returning identical readings, feed looks frozen"
+            );
+        }
+    }
+
+    /// Glitched readings are dropped instead of being stored and published
+    /// as spikes.
+    fn is_plausible(&self, raw_data: &RawPVData, state: &str) -> bool {
+        match raw_data.validate(&self.config.plausibility_limits) {
+            Ok(()) => true,
+            Err(problems) => {
+                let detail = problems.join("; ");
+                warn!(state, problems = %detail, "Implausible reading, skipping cycle");
+                self.log_cycle_skipped(state, SkipReason::Implausible, &detail);
+                false
+            }
+        }
+    }
+
+    /// Power values of the reading, blended with the external meter if one
+    /// is configured. An unreachable meter leaves the inverter value.
+    async fn process_power(&self, raw_data: RawPVData) -> ProcessedData {
+        let mut processed_data = ProcessedData::process_raw(raw_data, &self.config.battery_config);
+        let Some(meter) = &self.collection.external_meter else {
+            return processed_data;
+        };
+
+        match meter.read_power().await {
+            Ok(external) => processed_data
+                .apply_external_consumption(external, self.config.external_meter_config.mode),
+            Err(e) => warn!(
+                error = %e,
+                "External meter unavailable, using the inverter consumption"
+            ),
+        }
+        processed_data
+    }
+
+    /// Compares the energy counters with the previous cycle so a reset
+    /// shows up in the record and the metrics.
+    fn process_history(&mut self, raw_data: RawPVData) -> DataHistory {
+        let energy_data = raw_data.energy_data.clone();
+        let data_history = DataHistory::process_raw(
+            raw_data,
+            &self.config.battery_config,
+            self.collection.last_energy.as_ref(),
+        );
+        if data_history.counter_reset {
+            self.trackers.metrics.record_counter_reset();
+        }
+        self.collection.last_energy = Some(energy_data);
+        data_history
+    }
+
+    /// Without a storage backend there is no cache and the reading is
+    /// dropped.
+    async fn store_to_cache(
+        &self,
+        power_data: &ProcessedData,
+        energy_data: &DataHistory,
+    ) -> Result<()> {
+        let Some(cache) = &self.storage.cache else {
+            debug!("No cache configured, reading is not kept");
+            return Ok(());
+        };
+
+        cache
+            .store_power_data(power_data)
+            .await
+            .wrap_err("Failed to cache power data")?;
+        cache
+            .store_energy_data(energy_data)
+            .await
+            .wrap_err("Failed to cache energy data")?;
+        self.trackers.metrics.record_cached(2);
+        Ok(())
+    }
+
+    /// Moves the SQLite cache to Postgres right away, whatever the state.
+    /// None without cache or database, or while Postgres is unreachable;
+    /// the rows then stay cached for the next attempt.
+    pub async fn force_cache_sync(&self) -> Result<Option<SyncResult>> {
+        let (Some(cache), Some(pgdb)) = (&self.storage.cache, &self.storage.pgdb) else {
+            info!("Forced cache sync skipped, no cache or database configured");
+            return Ok(None);
+        };
+
+        if !self.storage.sink.health_check().await.unwrap_or(false) {
+            info!("Forced cache sync skipped, PostgreSQL unreachable");
+            return Ok(None);
+        }
+
+        let result = cache.sync_to_postgres(pgdb).await?;
+        info!(
+            records_synced = result.records_synced,
+            duration_ms = result.duration_ms,
+            "Forced cache sync completed"
+        );
+        Ok(Some(result))
+    }
+
+    /// Response payload of a `sync` command.
+    async fn force_cache_sync_response(&self) -> serde_json::Value {
+        let timestamp = chrono::Utc::now().to_rfc3339();
+        match self.force_cache_sync().await {
+            Ok(Some(result)) => serde_json::json!({
+                "command": SYNC_PAYLOAD,
+                "status": "ok",
+                "records_synced": result.records_synced,
+                "duration_ms": result.duration_ms,
+                "success": result.success,
+                "timestamp": timestamp
+            }),
+            Ok(None) => serde_json::json!({
+                "command": SYNC_PAYLOAD,
+                "status": "skipped",
+                "reason": "database unavailable",
+                "timestamp": timestamp
+            }),
+            Err(e) => {
+                warn!("Forced cache sync failed: {}", e);
+                serde_json::json!({
+                    "command": SYNC_PAYLOAD,
+                    "status": "error",
+                    "error": e.to_string(),
+                    "timestamp": timestamp
+                })
+            }
+        }
+    }
+
+    async fn sync_cache(&self) -> Result<()> {
+        match (&self.storage.cache, &self.storage.pgdb) {
+            (Some(cache), Some(pgdb)) => cache.sync_to_postgres(pgdb).await.map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn enforce_cache_limit(&self) {
+        let Some(cache) = &self.storage.cache else {
+            return;
+        };
+
+        if let Err(e) = cache.enforce_size_limit().await {
+            warn!("Failed to enforce cache size limit: {}", e);
+        }
+    }
+
+    /// With `mirror_to_cache` the reading lands in the cache before Postgres
+    /// sees it, so it survives a crash in the middle of the cycle.
+    async fn mirror_to_cache(
+        &self,
+        power_data: &ProcessedData,
+        energy_data: &DataHistory,
+    ) -> Option<(Option<i64>, Option<i64>)> {
+        let cache = self.storage.cache.as_ref()?;
+        if !self.config.sqlite_cache_config.mirror_to_cache {
+            return None;
+        }
+
+        let power_id = match cache.store_power_data(power_data).await {
+            Ok(id) => Some(id),
+            Err(e) => {
+                warn!("Failed to mirror power data to cache: {}", e);
+                None
+            }
+        };
+        let energy_id = match cache.store_energy_data(energy_data).await {
+            Ok(id) => Some(id),
+            Err(e) => {
+                warn!("Failed to mirror energy data to cache: {}", e);
+                None
+            }
+        };
+        Some((power_id, energy_id))
+    }
+
+    /// Drops the mirror copy after the Postgres write. Confirmed rows are
+    /// synced already, a failed write moves on to DegradedNoDB or CacheOnly,
+    /// which cache the reading themselves.
+    async fn release_mirrored(&self, mirrored: Option<(Option<i64>, Option<i64>)>) {
+        let (Some((power_id, energy_id)), Some(cache)) = (mirrored, &self.storage.cache) else {
+            return;
+        };
+        if let Err(e) = cache.remove_synced(power_id, energy_id).await {
+            warn!("Failed to remove mirrored rows from cache: {}", e);
+        }
+    }
+
+    async fn save_snapshot(&mut self, power_data: &ProcessedData, energy_data: &DataHistory) {
+        self.publish.live_feed.publish(power_data);
+        let snapshot = Snapshot::new(power_data, energy_data);
+        if let Err(e) = snapshot.save(&self.config.snapshot_path).await {
+            warn!("Failed to persist snapshot: {}", e);
+        }
+        self.collection.latest_snapshot = Some(snapshot);
+    }
+
+    async fn publish_change_events(&mut self, data: &ProcessedData) {
+        if !self.detectors.change.is_enabled() {
+            return;
+        }
+
+        let changes = self.detectors.change.detect(data);
+        if !changes.is_empty() {
+            self.publish.client.publish_changes(&changes).await;
+        }
+    }
+
+    /// Logs and publishes grid outage edges. Runs in every state so the
+    /// outage duration stays correct while a service is down.
+    async fn track_grid_outage(&mut self, data: &ProcessedData) {
+        if !self.detectors.outage.is_enabled() {
+            return;
+        }
+
+        let Some(event) = self
+            .detectors
+            .outage
+            .update(data.grid_connected(), chrono::Utc::now())
+        else {
+            return;
+        };
+
+        match &event {
+            GridEvent::OutageStarted { started_at } => {
+                warn!(started_at = %started_at, "Grid outage detected")
+            }
+            GridEvent::OutageEnded {
+                started_at,
+                duration_secs,
+                ..
+            } => info!(started_at = %started_at, duration_secs, "Grid restored"),
+        }
+        self.publish.client.publish_grid_event(&event).await;
+    }
+
+    /// Attributes the grid energy since the last cycle to the tariff windows.
+    /// Increments are kept until they could be written to Postgres.
+    async fn record_tariff(&mut self, data: &DataHistory, store: bool, publish: bool) {
+        if !self.trackers.tariff.is_enabled() {
+            return;
+        }
+
+        self.trackers.tariff.push(chrono::Local::now(), data);
+
+        match &self.storage.pgdb {
+            Some(pgdb) if store && !self.trackers.tariff.pending().is_empty() => {
+                match pgdb.add_tariff_energy(self.trackers.tariff.pending()).await {
+                    Ok(()) => self.trackers.tariff.clear_pending(),
+                    Err(e) => warn!("Failed to store tariff energy, keeping it for later: {}", e),
+                }
+            }
+            // Nothing will ever store them
+            None => self.trackers.tariff.clear_pending(),
+            Some(_) => {}
+        }
+
+        if publish {
+            self.publish
+                .client
+                .publish_tariff(&self.trackers.tariff.payload())
+                .await;
+        }
+    }
+
+    /// Energy since local midnight. The baseline is saved whenever it moves
+    /// so a restart keeps the totals of the day.
+    async fn record_daily_totals(&mut self, data: &DataHistory, publish: bool) {
+        if !self.trackers.daily_totals.is_enabled() {
+            return;
+        }
+
+        if self
+            .trackers
+            .daily_totals
+            .push(self.config.local_now(), data)
+            && let Err(e) = self.trackers.daily_totals.save().await
+        {
+            warn!("Failed to save daily baseline: {}", e);
+        }
+
+        if publish {
+            self.publish
+                .client
+                .publish_daily_totals(&self.trackers.daily_totals.payload())
+                .await;
+        }
+    }
+
+    async fn publish_efficiency(&mut self, data: &DataHistory) {
+        if !self.trackers.efficiency.is_enabled() {
+            return;
+        }
+
+        if let Some(efficiency) = self.trackers.efficiency.push(chrono::Utc::now(), data) {
+            let payload = self.trackers.efficiency.payload(efficiency);
+            self.publish.client.publish_efficiency(&payload).await;
+        }
+    }
+
+    async fn handle_incoming_commands(&self) {
+        for message in self.publish.client.drain_incoming().await {
+            if self.publish.client.is_birth_message(&message) {
+                info!("Home Assistant came online, re-sending discovery");
+                if let Err(e) = setup_discovery(&self.publish.client, &self.config).await {
+                    warn!("Failed to re-send discovery: {}", e);
+                }
+                self.publish.client.publish_availability(true).await;
+                continue;
+            }
+
+            if self.publish.client.is_sync_request(&message) {
+                let response = self.force_cache_sync_response().await;
+                self.publish
+                    .client
+                    .publish_command_response(&response)
+                    .await;
+                continue;
+            }
+
+            if let Some(command) = AdminCommand::parse(&message) {
+                let response = admin::execute(
+                    &command,
+                    self.storage.cache.as_ref(),
+                    self.config.mqtt_config.admin_token.as_deref(),
+                )
+                .await;
+                self.publish
+                    .client
+                    .publish_command_response(&response)
+                    .await;
+            }
+        }
     }
 
     pub async fn check_mqtt_health(&self) -> MQTTHealthStatus {
-        self.mqtt_client.get_health_status().await
+        self.publish.client.get_health_status().await
     }
 
     pub async fn check_postgres_health(&self) -> Result<bool> {
-        match self.pgdb.health_check().await {
-            Ok(crate::db::PostgresHealth::Healthy) => Ok(true),
-            _ => Ok(false),
-        }
+        Ok(self.storage.sink.health_check().await.unwrap_or(false))
     }
 }
 
@@ -460,6 +1419,15 @@ impl<S: HealthState> Coordinator<S> {
 // ENUM FOR PATTERN MATCHING IN MAIN LOOP
 // =============================================================================
 
+/// Names published for the Home Assistant enum sensor, one per `HealthState`.
+pub const HEALTH_STATE_OPTIONS: [&str; 5] = [
+    "Healthy",
+    "DegradedNoDB",
+    "DegradedNoMqtt",
+    "CacheOnly",
+    "Shutdown",
+];
+
 #[derive(Debug)]
 pub enum CoordinatorKind {
     Healthy(Coordinator<Healthy>),
@@ -470,7 +1438,387 @@ pub enum CoordinatorKind {
 }
 
 impl CoordinatorKind {
+    /// Like `Coordinator::start_with`, but begins degraded when PostgreSQL or
+    /// MQTT did not come up instead of assuming both are healthy.
+    pub async fn start_with(config: Config) -> Result<Self> {
+        let (coordinator, report) = Coordinator::start_reporting(config).await?;
+        Ok(coordinator.into_startup_state(report))
+    }
+
+    /// Moves into the state `transition` asks for and records the change
+    /// in the transition log. A transition that does not apply to the
+    /// current state leaves it unchanged.
+    pub async fn apply_transition(self, transition: HealthStateTransition) -> Self {
+        let from = self.state_name();
+        let reason = transition.reason(&self);
+        let next = match transition {
+            HealthStateTransition::ToHealthy => match self {
+                CoordinatorKind::DegradedNoDB(c) => CoordinatorKind::Healthy(c.to_healthy().await),
+                CoordinatorKind::DegradedNoMqtt(c) => CoordinatorKind::Healthy(c.to_healthy()),
+                CoordinatorKind::CacheOnly(c) => CoordinatorKind::Healthy(c.to_healthy().await),
+                other => other,
+            },
+
+            HealthStateTransition::ToDegradedNoDB(power_data, energy_data) => match self {
+                CoordinatorKind::Healthy(c) => CoordinatorKind::DegradedNoDB(
+                    c.to_degraded_no_db(power_data, energy_data).await,
+                ),
+                CoordinatorKind::DegradedNoMqtt(c) => {
+                    CoordinatorKind::DegradedNoDB(c.to_cache_only().to_degraded_no_db())
+                }
+                CoordinatorKind::CacheOnly(c) => {
+                    CoordinatorKind::DegradedNoDB(c.to_degraded_no_db())
+                }
+                other => other,
+            },
+
+            HealthStateTransition::ToDegradedNoMqtt => match self {
+                CoordinatorKind::Healthy(c) => {
+                    CoordinatorKind::DegradedNoMqtt(c.to_degraded_no_mqtt())
+                }
+                CoordinatorKind::DegradedNoDB(c) => {
+                    CoordinatorKind::DegradedNoMqtt(c.to_cache_only().to_degraded_no_mqtt())
+                }
+                CoordinatorKind::CacheOnly(c) => {
+                    CoordinatorKind::DegradedNoMqtt(c.to_degraded_no_mqtt())
+                }
+                other => other,
+            },
+
+            HealthStateTransition::ToCacheOnly(power_data, energy_data) => match self {
+                CoordinatorKind::Healthy(c) => {
+                    CoordinatorKind::CacheOnly(c.to_cache_only(power_data, energy_data).await)
+                }
+                CoordinatorKind::DegradedNoDB(c) => CoordinatorKind::CacheOnly(c.to_cache_only()),
+                CoordinatorKind::DegradedNoMqtt(c) => CoordinatorKind::CacheOnly(c.to_cache_only()),
+                other => other,
+            },
+
+            HealthStateTransition::ToShutdown => match self {
+                CoordinatorKind::Healthy(c) => CoordinatorKind::Shutdown(c.to_shutdown()),
+                CoordinatorKind::DegradedNoDB(c) => CoordinatorKind::Shutdown(c.to_shutdown()),
+                CoordinatorKind::DegradedNoMqtt(c) => CoordinatorKind::Shutdown(c.to_shutdown()),
+                CoordinatorKind::CacheOnly(c) => CoordinatorKind::Shutdown(c.to_shutdown()),
+                other => other,
+            },
+        };
+
+        if next.state_name() != from {
+            next.record_transition(from, reason).await;
+            next.notify_transition(from, reason);
+        }
+
+        if let CoordinatorKind::Healthy(c) = &next
+            && c.publish.client.discovery_pending()
+        {
+            info!("Retrying Home Assistant discovery");
+            if let Err(e) = setup_discovery(&c.publish.client, &c.config).await {
+                warn!(error = %e, "Discovery still incomplete");
+            }
+        }
+        next
+    }
+
+    /// Records the state the coordinator started in, with what was down.
+    pub async fn record_startup(&self) {
+        let reason = match self {
+            CoordinatorKind::Healthy(_) => "Startup",
+            CoordinatorKind::DegradedNoDB(_) => "PostgreSQL unavailable at startup",
+            CoordinatorKind::DegradedNoMqtt(_) => "MQTT unavailable at startup",
+            CoordinatorKind::CacheOnly(_) => "PostgreSQL and MQTT unavailable at startup",
+            CoordinatorKind::Shutdown(_) => "Shutdown at startup",
+        };
+        self.record_transition("Starting", reason).await;
+        self.notify_transition("Starting", reason);
+    }
+
+    /// Best effort, a failed write only logs a warning so the transition
+    /// itself never fails on the log.
+    async fn record_transition(&self, from: &str, reason: &str) {
+        let (Some(cache), _) = self.cache_and_snapshot() else {
+            return;
+        };
+        if let Err(e) = cache
+            .record_transition(from, self.state_name(), reason)
+            .await
+        {
+            warn!("Failed to record state transition: {:?}", e);
+        }
+    }
+
+    /// Best effort like `record_transition`. Sent from its own task, a slow
+    /// webhook never holds up the cycle.
+    fn notify_transition(&self, from: &str, reason: &str) {
+        let Some(notifier) = self.notifier().cloned() else {
+            return;
+        };
+        let (_, _, config) = self.services();
+        let event = TransitionEvent {
+            device_id: config.device_id.clone(),
+            from_state: from.to_string(),
+            state: self.state_name().to_string(),
+            reason: reason.to_string(),
+            timestamp: chrono::Utc::now(),
+        };
+        tokio::spawn(async move {
+            if let Err(e) = notifier.notify(&event).await {
+                warn!("Failed to send transition notification: {:?}", e);
+            }
+        });
+    }
+
+    pub fn cycle_interval(&self) -> Duration {
+        match self {
+            CoordinatorKind::Healthy(c) => c.config.cycle_interval(false),
+            CoordinatorKind::DegradedNoDB(c) => c.config.cycle_interval(true),
+            CoordinatorKind::DegradedNoMqtt(c) => c.config.cycle_interval(true),
+            CoordinatorKind::CacheOnly(c) => c.config.cycle_interval(true),
+            CoordinatorKind::Shutdown(c) => c.config.cycle_interval(false),
+        }
+    }
+
+    pub fn mqtt_client(&self) -> &SolarMqttClient {
+        self.services().0
+    }
+
+    /// Handles admin commands and Home Assistant birth messages received
+    /// since the last call.
+    pub async fn handle_incoming_commands(&self) {
+        match self {
+            CoordinatorKind::Healthy(c) => c.handle_incoming_commands().await,
+            CoordinatorKind::DegradedNoDB(c) => c.handle_incoming_commands().await,
+            CoordinatorKind::DegradedNoMqtt(c) => c.handle_incoming_commands().await,
+            CoordinatorKind::CacheOnly(c) => c.handle_incoming_commands().await,
+            CoordinatorKind::Shutdown(_) => {}
+        }
+    }
+
+    pub fn state_name(&self) -> &'static str {
+        match self {
+            CoordinatorKind::Healthy(_) => HEALTH_STATE_OPTIONS[0],
+            CoordinatorKind::DegradedNoDB(_) => HEALTH_STATE_OPTIONS[1],
+            CoordinatorKind::DegradedNoMqtt(_) => HEALTH_STATE_OPTIONS[2],
+            CoordinatorKind::CacheOnly(_) => HEALTH_STATE_OPTIONS[3],
+            CoordinatorKind::Shutdown(_) => HEALTH_STATE_OPTIONS[4],
+        }
+    }
+
+    fn services(&self) -> (&SolarMqttClient, Option<&PostgresDatabase>, &Config) {
+        match self {
+            CoordinatorKind::Healthy(c) => (&c.publish.client, c.storage.pgdb.as_ref(), &c.config),
+            CoordinatorKind::DegradedNoDB(c) => {
+                (&c.publish.client, c.storage.pgdb.as_ref(), &c.config)
+            }
+            CoordinatorKind::DegradedNoMqtt(c) => {
+                (&c.publish.client, c.storage.pgdb.as_ref(), &c.config)
+            }
+            CoordinatorKind::CacheOnly(c) => {
+                (&c.publish.client, c.storage.pgdb.as_ref(), &c.config)
+            }
+            CoordinatorKind::Shutdown(c) => (&c.publish.client, c.storage.pgdb.as_ref(), &c.config),
+        }
+    }
+
+    fn notifier(&self) -> Option<&Arc<dyn Notifier>> {
+        match self {
+            CoordinatorKind::Healthy(c) => c.publish.notifier.as_ref(),
+            CoordinatorKind::DegradedNoDB(c) => c.publish.notifier.as_ref(),
+            CoordinatorKind::DegradedNoMqtt(c) => c.publish.notifier.as_ref(),
+            CoordinatorKind::CacheOnly(c) => c.publish.notifier.as_ref(),
+            CoordinatorKind::Shutdown(c) => c.publish.notifier.as_ref(),
+        }
+    }
+
+    fn cache_and_snapshot(&self) -> (Option<&SqliteCache>, Option<&Snapshot>) {
+        match self {
+            CoordinatorKind::Healthy(c) => (
+                c.storage.cache.as_ref(),
+                c.collection.latest_snapshot.as_ref(),
+            ),
+            CoordinatorKind::DegradedNoDB(c) => (
+                c.storage.cache.as_ref(),
+                c.collection.latest_snapshot.as_ref(),
+            ),
+            CoordinatorKind::DegradedNoMqtt(c) => (
+                c.storage.cache.as_ref(),
+                c.collection.latest_snapshot.as_ref(),
+            ),
+            CoordinatorKind::CacheOnly(c) => (
+                c.storage.cache.as_ref(),
+                c.collection.latest_snapshot.as_ref(),
+            ),
+            CoordinatorKind::Shutdown(c) => (
+                c.storage.cache.as_ref(),
+                c.collection.latest_snapshot.as_ref(),
+            ),
+        }
+    }
+
+    pub fn collection_latency(&self) -> &LatencyHistogram {
+        match self {
+            CoordinatorKind::Healthy(c) => &c.collection.latency,
+            CoordinatorKind::DegradedNoDB(c) => &c.collection.latency,
+            CoordinatorKind::DegradedNoMqtt(c) => &c.collection.latency,
+            CoordinatorKind::CacheOnly(c) => &c.collection.latency,
+            CoordinatorKind::Shutdown(c) => &c.collection.latency,
+        }
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        match self {
+            CoordinatorKind::Healthy(c) => &c.trackers.metrics,
+            CoordinatorKind::DegradedNoDB(c) => &c.trackers.metrics,
+            CoordinatorKind::DegradedNoMqtt(c) => &c.trackers.metrics,
+            CoordinatorKind::CacheOnly(c) => &c.trackers.metrics,
+            CoordinatorKind::Shutdown(c) => &c.trackers.metrics,
+        }
+    }
+
+    fn stale_detector(&self) -> &StaleDetector {
+        match self {
+            CoordinatorKind::Healthy(c) => &c.detectors.stale,
+            CoordinatorKind::DegradedNoDB(c) => &c.detectors.stale,
+            CoordinatorKind::DegradedNoMqtt(c) => &c.detectors.stale,
+            CoordinatorKind::CacheOnly(c) => &c.detectors.stale,
+            CoordinatorKind::Shutdown(c) => &c.detectors.stale,
+        }
+    }
+
+    pub fn live_feed(&self) -> &LiveFeed {
+        match self {
+            CoordinatorKind::Healthy(c) => &c.publish.live_feed,
+            CoordinatorKind::DegradedNoDB(c) => &c.publish.live_feed,
+            CoordinatorKind::DegradedNoMqtt(c) => &c.publish.live_feed,
+            CoordinatorKind::CacheOnly(c) => &c.publish.live_feed,
+            CoordinatorKind::Shutdown(c) => &c.publish.live_feed,
+        }
+    }
+
+    async fn publish_health_state(&self) {
+        let (mqtt_client, _, config) = self.services();
+
+        if config.mqtt_config.publish_health_state {
+            mqtt_client.publish_health_state(self.state_name()).await;
+        }
+    }
+
+    /// Only Healthy and DegradedNoDB, the other states would just log a
+    /// failed publish every cycle.
+    fn mqtt_usable(&self) -> bool {
+        matches!(
+            self,
+            CoordinatorKind::Healthy(_) | CoordinatorKind::DegradedNoDB(_)
+        )
+    }
+
+    async fn publish_collection_latency(&self) {
+        if !self.mqtt_usable() {
+            return;
+        }
+
+        if let Some(payload) = self.collection_latency().payload() {
+            self.mqtt_client()
+                .publish_collection_latency(&payload)
+                .await;
+        }
+    }
+
+    /// Mirrors the status just written by `update_status`.
+    async fn publish_diagnostics(&self, status: &SharedStatus) {
+        if !self.mqtt_usable() {
+            return;
+        }
+
+        let snapshot = {
+            let status = status.lock().await;
+            DiagnosticsSnapshot {
+                state: status.state.clone(),
+                postgres_health: status.postgres.clone(),
+                mqtt_health: status.mqtt.clone(),
+                db_consecutive_failures: status.postgres_consecutive_failures,
+                cache_records: status.cache_backlog,
+                data_stale: status.data_stale,
+            }
+        };
+        self.mqtt_client().publish_diagnostics(&snapshot).await;
+    }
+
+    async fn update_status(&self, status: &SharedStatus, cycle_completed: bool) {
+        let (mqtt_client, pgdb, _) = self.services();
+        let (cache, latest_snapshot) = self.cache_and_snapshot();
+        let postgres_state = match pgdb {
+            Some(pgdb) => Some(pgdb.get_state().await),
+            None => None,
+        };
+        let mqtt_state = mqtt_client.get_health_state().await;
+        let cache_stats = match cache {
+            Some(cache) => cache.get_cache_stats().await.ok(),
+            None => None,
+        };
+        let transitions = match cache {
+            Some(cache) => cache
+                .recent_transitions(server::RECENT_TRANSITIONS)
+                .await
+                .ok(),
+            None => None,
+        };
+
+        let mut status = status.lock().await;
+        status.state = self.state_name().to_string();
+        match postgres_state {
+            Some(postgres_state) => {
+                status.postgres = format!("{:?}", postgres_state.health);
+                status.postgres_consecutive_failures = postgres_state.consecutive_failures;
+            }
+            None => status.postgres = "Disabled".to_string(),
+        }
+        status.mqtt = format!("{:?}", mqtt_state.status);
+        self.metrics()
+            .record_mqtt_buffer_drops(mqtt_client.offline_buffer().dropped());
+        status.mqtt_failed_publish_count = mqtt_state.failed_publish_count;
+        if let Some(stats) = cache_stats {
+            status.cache_backlog = stats.power_records_cached + stats.energy_records_cached;
+        }
+        if let Some(transitions) = transitions {
+            status.transitions = transitions;
+        }
+        if cycle_completed {
+            status.last_successful_cycle = Some(chrono::Utc::now());
+        }
+        if let Some(snapshot) = latest_snapshot {
+            status.push_reading(snapshot);
+        }
+        status.collection_latency = self.collection_latency().stats();
+        status.data_stale = self.stale_detector().is_stale();
+    }
+
+    /// Credits the time since the last call to the previous state and
+    /// persists the totals.
+    async fn account_state_time(&self, tracker: &mut StateTimeTracker) {
+        if !tracker.is_enabled() {
+            return;
+        }
+
+        tracker.update(self.state_name(), chrono::Utc::now(), self.metrics());
+        if let Err(e) = tracker.save().await {
+            warn!("Failed to persist state times: {:?}", e);
+        }
+    }
+
+    /// Drives whatever state we are in into `Shutdown` so `cleanup()` can run.
+    pub fn into_shutdown(self) -> Coordinator<Shutdown> {
+        match self {
+            CoordinatorKind::Healthy(c) => c.to_shutdown(),
+            CoordinatorKind::DegradedNoDB(c) => c.to_shutdown(),
+            CoordinatorKind::DegradedNoMqtt(c) => c.to_shutdown(),
+            CoordinatorKind::CacheOnly(c) => c.to_shutdown(),
+            CoordinatorKind::Shutdown(c) => c,
+        }
+    }
+
     pub async fn run_cycle(&mut self) -> Result<CoordinatorResult> {
+        self.handle_incoming_commands().await;
+
         match self {
             CoordinatorKind::Healthy(c) => c.run_cycle().await,
             CoordinatorKind::DegradedNoDB(c) => c.run_cycle().await,
@@ -485,108 +1833,366 @@ impl CoordinatorKind {
 // MAIN LOOP IMPLEMENTATION
 // =============================================================================
 
+/// Starts a coordinator per config side by side. If one of them fails to
+/// start, the ones already running are shut down properly before the first
+/// error is returned.
+pub async fn start_all(configs: Vec<Config>) -> Result<Vec<CoordinatorKind>> {
+    let started = futures::future::join_all(configs.into_iter().map(|config| async move {
+        let device_id = config.device_id.clone();
+        (device_id, CoordinatorKind::start_with(config).await)
+    }))
+    .await;
+
+    let mut coordinators = Vec::new();
+    let mut first_error = None;
+    for (device_id, result) in started {
+        match result {
+            Ok(coordinator) => coordinators.push(coordinator),
+            Err(e) => {
+                error!(device_id, "Coordinator failed to start: {:?}", e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    let Some(error) = first_error else {
+        return Ok(coordinators);
+    };
+    for coordinator in coordinators {
+        let device_id = coordinator.services().2.device_id.clone();
+        if let Err(e) = coordinator.into_shutdown().run_cycle().await {
+            warn!(
+                device_id,
+                "Cleanup after failed start did not finish: {:?}", e
+            );
+        }
+    }
+    Err(error)
+}
+
+/// Runs one coordinator per configured inverter side by side. The HTTP
+/// status server reports on all of them.
 pub async fn run_coordinator() -> Result<()> {
-    info!("Starting coordinator main loop");
+    let config = Config::new()?;
+    config.validate()?;
+    let configs = config.per_inverter();
+    info!(inverters = configs.len(), "Starting coordinator main loop");
+
+    let coordinators = start_all(configs).await?;
+    let statuses: Vec<SharedStatus> = coordinators
+        .iter()
+        .map(|_| SharedStatus::default())
+        .collect();
+
+    let (_, _, config) = coordinators[0].services();
+    let bind_addr = config.http_bind_addr.clone();
+    let app_state = AppState {
+        devices: coordinators
+            .iter()
+            .zip(&statuses)
+            .map(|(coordinator, status)| DeviceState {
+                device_id: coordinator.services().2.device_id.clone(),
+                status: status.clone(),
+                metrics: coordinator.metrics().clone(),
+                live: coordinator.live_feed().clone(),
+            })
+            .collect(),
+    };
+    tokio::spawn(async move {
+        if let Err(e) = server::serve(&bind_addr, app_state).await {
+            error!("HTTP status server stopped: {:?}", e);
+        }
+    });
+
+    // Each inverter runs on its own task. When one fails the others are
+    // shut down properly, so their caches are flushed before the error is
+    // returned.
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(());
+    let mut main_loops = tokio::task::JoinSet::new();
+    for (coordinator, status) in coordinators.into_iter().zip(statuses) {
+        let device_id = coordinator.services().2.device_id.clone();
+        let mut shutdown_rx = shutdown_rx.clone();
+        main_loops.spawn(async move {
+            let result = run_until_shutdown(coordinator, status, async move {
+                let _ = shutdown_rx.changed().await;
+            })
+            .await;
+            (device_id, result)
+        });
+    }
+
+    let mut first_error = None;
+    loop {
+        tokio::select! {
+            joined = main_loops.join_next() => {
+                let error = match joined {
+                    None => break,
+                    Some(Ok((device_id, Ok(())))) => {
+                        info!(device_id, "Coordinator stopped");
+                        continue;
+                    }
+                    Some(Ok((device_id, Err(e)))) => {
+                        error!(device_id, "Coordinator failed, shutting down the others: {:?}", e);
+                        e
+                    }
+                    Some(Err(e)) => {
+                        error!("Coordinator task panicked, shutting down the others: {}", e);
+                        eyre!("Coordinator task panicked: {}", e)
+                    }
+                };
+                let _ = shutdown_tx.send(());
+                first_error.get_or_insert(error);
+            }
+            _ = shutdown_signal() => {
+                let _ = shutdown_tx.send(());
+            }
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Resolves on SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received SIGINT"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
 
-    let mut coordinator = CoordinatorKind::Healthy(Coordinator::start().await?);
+/// Runs the main loop until the coordinator shuts itself down or `shutdown`
+/// resolves. A running cycle is always finished before shutting down.
+pub async fn run_until_shutdown(
+    mut coordinator: CoordinatorKind,
+    status: SharedStatus,
+    shutdown: impl std::future::Future<Output = ()>,
+) -> Result<()> {
+    tokio::pin!(shutdown);
+    let refresh = coordinator.mqtt_client().refresh_signal();
+    let (_, _, config) = coordinator.services();
+    let mut state_time =
+        StateTimeTracker::load(config.state_time_config.clone(), coordinator.metrics()).await;
+
+    coordinator.record_startup().await;
+    coordinator.publish_health_state().await;
+    coordinator.update_status(&status, false).await;
+    coordinator.account_state_time(&mut state_time).await;
 
     loop {
         coordinator = match coordinator.run_cycle().await? {
-            CoordinatorResult::Continue => coordinator,
+            CoordinatorResult::Continue => {
+                coordinator.update_status(&status, true).await;
+                coordinator.publish_diagnostics(&status).await;
+                coordinator.publish_collection_latency().await;
+                coordinator.account_state_time(&mut state_time).await;
+                coordinator
+            }
 
             CoordinatorResult::TransitionTo(transition) => {
                 info!("Performing state transition: {:?}", transition);
 
-                match transition {
-                    HealthStateTransition::ToHealthy => match coordinator {
-                        CoordinatorKind::DegradedNoDB(c) => {
-                            CoordinatorKind::Healthy(c.to_healthy().await)
-                        }
-                        CoordinatorKind::DegradedNoMqtt(c) => {
-                            CoordinatorKind::Healthy(c.to_healthy())
-                        }
-                        CoordinatorKind::CacheOnly(c) => {
-                            CoordinatorKind::Healthy(c.to_healthy().await)
-                        }
-                        other => other,
-                    },
-
-                    HealthStateTransition::ToDegradedNoDB(power_data, energy_data) => {
-                        match coordinator {
-                            CoordinatorKind::Healthy(c) => CoordinatorKind::DegradedNoDB(
-                                c.to_degraded_no_db(power_data, energy_data).await,
-                            ),
-                            CoordinatorKind::DegradedNoMqtt(c) => {
-                                CoordinatorKind::DegradedNoDB(c.to_cache_only().to_degraded_no_db())
-                            }
-                            CoordinatorKind::CacheOnly(c) => {
-                                CoordinatorKind::DegradedNoDB(c.to_degraded_no_db())
-                            }
-                            other => other,
-                        }
-                    }
-
-                    HealthStateTransition::ToDegradedNoMqtt => match coordinator {
-                        CoordinatorKind::Healthy(c) => {
-                            CoordinatorKind::DegradedNoMqtt(c.to_degraded_no_mqtt())
-                        }
-                        CoordinatorKind::DegradedNoDB(c) => {
-                            CoordinatorKind::DegradedNoMqtt(c.to_cache_only().to_degraded_no_mqtt())
-                        }
-                        CoordinatorKind::CacheOnly(c) => {
-                            CoordinatorKind::DegradedNoMqtt(c.to_degraded_no_mqtt())
-                        }
-                        other => other,
-                    },
-
-                    HealthStateTransition::ToCacheOnly(power_data, energy_data) => {
-                        match coordinator {
-                            CoordinatorKind::Healthy(c) => CoordinatorKind::CacheOnly(
-                                c.to_cache_only(power_data, energy_data).await,
-                            ),
-                            CoordinatorKind::DegradedNoDB(c) => {
-                                CoordinatorKind::CacheOnly(c.to_cache_only())
-                            }
-                            CoordinatorKind::DegradedNoMqtt(c) => {
-                                CoordinatorKind::CacheOnly(c.to_cache_only())
-                            }
-                            other => other,
-                        }
-                    }
+                let next = coordinator.apply_transition(transition).await;
 
-                    HealthStateTransition::ToShutdown => match coordinator {
-                        CoordinatorKind::Healthy(c) => CoordinatorKind::Shutdown(c.to_shutdown()),
-                        CoordinatorKind::DegradedNoDB(c) => {
-                            CoordinatorKind::Shutdown(c.to_shutdown())
-                        }
-                        CoordinatorKind::DegradedNoMqtt(c) => {
-                            CoordinatorKind::Shutdown(c.to_shutdown())
-                        }
-                        CoordinatorKind::CacheOnly(c) => CoordinatorKind::Shutdown(c.to_shutdown()),
-                        other => other,
-                    },
-                }
+                next.publish_health_state().await;
+                next.update_status(&status, false).await;
+                next.publish_diagnostics(&status).await;
+                next.account_state_time(&mut state_time).await;
+                next
             }
 
             CoordinatorResult::Shutdown => {
                 info!("Shutdown requested, exiting main loop");
+                coordinator.account_state_time(&mut state_time).await;
                 break;
             }
         };
-        tokio::time::sleep(Duration::from_secs(60)).await;
+
+        tokio::select! {
+            _ = &mut shutdown => {
+                info!("Shutdown signal received, stopping after current cycle");
+                coordinator.account_state_time(&mut state_time).await;
+                let mut shutdown_coordinator = coordinator.into_shutdown();
+                status.lock().await.state = HEALTH_STATE_OPTIONS[4].to_string();
+                shutdown_coordinator.run_cycle().await?;
+                break;
+            }
+            _ = refresh.notified() => {
+                info!("Refresh requested, running cycle now");
+            }
+            _ = tokio::time::sleep(coordinator.cycle_interval()) => {}
+        }
     }
 
     info!("Coordinator main loop completed");
     Ok(())
 }
 
-async fn collect_raw_data_with_retry(basepath: &str) -> Result<RawPVData> {
+pub async fn run_once() -> Result<()> {
+    info!("Starting coordinator in one-shot mode");
+
+    let config = Config::new()?;
+    config.validate()?;
+    for config in config.per_inverter() {
+        let device_id = config.device_id.clone();
+        let mut coordinator = Coordinator::start_with(config).await?;
+        coordinator.run_single_cycle().await?;
+        info!(device_id, "Single cycle completed");
+    }
+
+    Ok(())
+}
+
+const MAX_RECOVERY_BACKOFF_SECS: u64 = 300;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Every discovery component enabled in `config`.
+pub fn discovery_components(
+    client: &SolarMqttClient,
+    config: &Config,
+) -> Result<Vec<DiscoveryComponent>> {
+    let mut components = client.discovery_components();
+
+    if config.mqtt_config.publish_device_attributes {
+        components.push(client.device_info_component());
+    }
+
+    if config.efficiency_config.enabled {
+        components.push(client.efficiency_component());
+    }
+
+    if config.tariff_config.enabled {
+        let windows = TariffTracker::new(config.tariff_config.clone())?.window_names();
+        components.extend(client.tariff_components(&windows));
+    }
+
+    if config.daily_totals_config.enabled {
+        components.extend(client.daily_totals_components());
+    }
+
+    if config.external_meter_config.url.is_some() {
+        components.extend(client.external_meter_components());
+    }
+
+    if config.storage_backend == StorageBackend::Postgres {
+        components.push(client.sync_button_component());
+    }
+
+    if config.battery_config.production_source == ProductionSource::Both {
+        components.extend(client.production_source_components());
+    }
+
+    if config.battery_config.power_limit_detection {
+        components.extend(client.battery_limit_components());
+    }
+
+    if config.battery_config.grid_charge_detection {
+        components.push(client.grid_charge_component());
+    }
+
+    if config.mqtt_config.publish_health_state {
+        components.push(client.health_state_component(&HEALTH_STATE_OPTIONS));
+    }
+
+    components.push(client.collection_latency_component());
+    components.extend(client.diagnostic_components());
+
+    Ok(components)
+}
+
+/// Publishes every discovery config enabled in `config`. Runs at startup and
+/// again whenever Home Assistant sends its birth message.
+/// Marks the discovery as pending on failure so the next recovery to
+/// Healthy sends it again.
+async fn setup_discovery(client: &SolarMqttClient, config: &Config) -> Result<()> {
+    let result = publish_discovery(client, config).await;
+    client.set_discovery_pending(result.is_err());
+    result
+}
+
+async fn publish_discovery(client: &SolarMqttClient, config: &Config) -> Result<()> {
+    info!(
+        mode = ?config.mqtt_config.discovery_mode,
+        "Setting up Home Assistant MQTT Discovery"
+    );
+    let components = discovery_components(client, config)?;
+
+    match config.mqtt_config.discovery_mode {
+        DiscoveryMode::Legacy => client.publish_components(&components).await?,
+        DiscoveryMode::Device => client.setup_device_discovery(&components).await?,
+    }
+
+    if config.mqtt_config.publish_device_attributes {
+        client
+            .publish_device_attributes(&config.device_attributes())
+            .await?;
+    }
+
+    info!("Home Assistant Discovery setup completed");
+    Ok(())
+}
+
+/// Delay before the next recovery attempt: base * 2^attempts, capped at 300s.
+pub(crate) fn recovery_delay(base_secs: u64, attempts: u32) -> Duration {
+    let factor = 2_u64.saturating_pow(attempts);
+    Duration::from_secs(
+        base_secs
+            .saturating_mul(factor)
+            .min(MAX_RECOVERY_BACKOFF_SECS),
+    )
+}
+
+/// Every attempt is timed into `latency`, failed ones included, so a slow
+/// LAN shows up even when the poll eventually times out.
+async fn collect_raw_data_with_retry(
+    config: &Config,
+    latency: &mut LatencyHistogram,
+) -> Result<RawPVData> {
     const MAX_RETRIES: u8 = 3;
     const BASE_DELAY_MS: u64 = 100;
 
     for attempt in 0..MAX_RETRIES {
-        match RawPVData::fill_raw(basepath).await {
+        let started = Instant::now();
+        let result = RawPVData::fill_raw(
+            &config.pv_baseaddress,
+            &config.collected_channels(),
+            &config.pv_auth,
+        )
+        .await;
+        latency.record(started.elapsed());
+
+        match result {
             Ok(data) => return Ok(data),
+            // Retrying with the same credentials cannot help
+            Err(e) if e.downcast_ref::<InverterAuthError>().is_some() => {
+                error!("Data collection failed: {:?}", e);
+                return Err(e);
+            }
             Err(e) => {
                 if attempt == MAX_RETRIES - 1 {
                     error!(
@@ -607,3 +2213,14 @@ async fn collect_raw_data_with_retry(basepath: &str) -> Result<RawPVData> {
     }
     unreachable!()
 }
+
+#[test]
+fn test_recovery_delay_backoff() {
+    let delays: Vec<u64> = (0..7)
+        .map(|attempts| recovery_delay(10, attempts).as_secs())
+        .collect();
+    assert_eq!(delays, vec![10, 20, 40, 80, 160, 300, 300]);
+
+    // Large counters must not overflow
+    assert_eq!(recovery_delay(10, u32::MAX).as_secs(), 300);
+}
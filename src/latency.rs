@@ -0,0 +1,117 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Collection durations kept by `LatencyHistogram::default()`.
+pub const LATENCY_WINDOW: usize = 100;
+
+/// Summary of the durations currently in the window, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub min_ms: u64,
+    pub avg_ms: f64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+/// Rolling window over the last inverter poll durations. Once the window is
+/// full the oldest sample is dropped, so the stats only ever describe the
+/// most recent polls.
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    capacity: usize,
+    samples: VecDeque<u64>,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new(LATENCY_WINDOW)
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn record(&mut self, duration: Duration) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        let millis = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        self.samples.push_back(millis);
+    }
+
+    /// None until the first sample arrived.
+    pub fn stats(&self) -> Option<LatencyStats> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<u64> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let total: u128 = sorted.iter().map(|ms| *ms as u128).sum();
+
+        // Nearest-rank percentile
+        let rank = (sorted.len() * 95).div_ceil(100);
+        Some(LatencyStats {
+            samples: sorted.len(),
+            min_ms: sorted[0],
+            avg_ms: total as f64 / sorted.len() as f64,
+            p95_ms: sorted[rank - 1],
+            max_ms: sorted[sorted.len() - 1],
+        })
+    }
+
+    pub fn payload(&self) -> Option<Value> {
+        let stats = self.stats()?;
+        let mut payload = json!(stats);
+        payload["avg_ms"] = json!((stats.avg_ms * 10.0).round() / 10.0);
+        payload["timestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        Some(payload)
+    }
+}
+
+#[test]
+fn test_latency_p95() {
+    let mut histogram = LatencyHistogram::default();
+    assert_eq!(histogram.stats(), None);
+
+    for ms in 1..=100 {
+        histogram.record(Duration::from_millis(ms));
+    }
+    let stats = histogram.stats().unwrap();
+    assert_eq!(stats.samples, 100);
+    assert_eq!(stats.min_ms, 1);
+    assert_eq!(stats.p95_ms, 95);
+    assert_eq!(stats.max_ms, 100);
+    assert_eq!(stats.avg_ms, 50.5);
+
+    // A slow streak pushes the oldest samples out of the window
+    for _ in 0..50 {
+        histogram.record(Duration::from_millis(1_000));
+    }
+    let stats = histogram.stats().unwrap();
+    assert_eq!(stats.samples, 100);
+    assert_eq!(stats.min_ms, 51);
+    assert_eq!(stats.p95_ms, 1_000);
+    assert_eq!(stats.max_ms, 1_000);
+
+    // Small windows still pick a sample inside the range
+    let mut small = LatencyHistogram::new(3);
+    for ms in [30, 10, 20, 40] {
+        small.record(Duration::from_millis(ms));
+    }
+    let stats = small.stats().unwrap();
+    assert_eq!((stats.min_ms, stats.p95_ms, stats.max_ms), (10, 40, 40));
+
+    let payload = small.payload().unwrap();
+    assert_eq!(payload["p95_ms"], 40);
+    assert_eq!(payload["avg_ms"], 23.3);
+}
@@ -0,0 +1,42 @@
+//! FENECON PV data collector. The binary runs the full coordinator with
+//! MQTT and PostgreSQL, [`PvMonitor`] reads and processes the inverter on
+//! its own for embedding the collector in another application.
+
+pub mod admin;
+pub mod bench;
+pub mod cache;
+pub mod calculator;
+pub mod changes;
+pub mod collector;
+pub mod config;
+pub mod daily;
+pub mod db;
+pub mod efficiency;
+pub mod gap;
+pub mod health;
+pub mod latency;
+pub mod logging;
+pub mod meter;
+pub mod metrics;
+pub mod monitor;
+pub mod mqtt;
+pub mod notify;
+pub mod outage;
+pub mod preflight;
+pub mod server;
+pub mod sink;
+pub mod smoothing;
+pub mod snapshot;
+pub mod stale;
+pub mod state_time;
+pub mod tariff;
+pub mod tui;
+
+#[cfg(test)]
+mod test;
+
+pub use calculator::{DataHistory, ProcessedData};
+pub use collector::RawPVData;
+pub use config::Config;
+pub use monitor::PvMonitor;
+pub use mqtt::SolarMqttClient;
@@ -0,0 +1,78 @@
+use color_eyre::{Result, eyre::eyre};
+use std::str::FromStr;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+
+/// Line format of the log output, chosen with `LOG_FORMAT`. `json` writes
+/// one object per event for Loki/ELK.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+    Compact,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            "compact" => Ok(LogFormat::Compact),
+            other => Err(format!("unknown log format '{}'", other)),
+        }
+    }
+}
+
+pub fn setup_logging_env(log_file: Option<&str>) -> Result<()> {
+    let format = match std::env::var("LOG_FORMAT") {
+        Ok(value) => value.parse().map_err(|e| eyre!("LOG_FORMAT: {}", e))?,
+        // Multi-line pretty output only makes sense on a terminal
+        Err(_) if log_file.is_some() => LogFormat::Compact,
+        Err(_) => LogFormat::Pretty,
+    };
+    let filter = EnvFilter::from_default_env();
+
+    let subscriber = match log_file {
+        Some(path) => {
+            if let Some(parent) = std::path::Path::new(path).parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            log_subscriber(format, filter, std::sync::Mutex::new(file), false)
+        }
+        None => log_subscriber(format, filter, std::io::stdout, true),
+    };
+    subscriber.init();
+    Ok(())
+}
+
+pub fn log_subscriber<W>(
+    format: LogFormat,
+    filter: EnvFilter,
+    writer: W,
+    ansi: bool,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = FmtSubscriber::builder()
+        .with_env_filter(filter)
+        .with_target(false)
+        .with_thread_ids(true)
+        .with_file(true)
+        .with_line_number(true)
+        .with_writer(writer);
+
+    match format {
+        LogFormat::Pretty => Box::new(builder.with_ansi(ansi).pretty().finish()),
+        LogFormat::Compact => Box::new(builder.with_ansi(ansi).compact().finish()),
+        LogFormat::Json => Box::new(builder.with_ansi(false).json().flatten_event(true).finish()),
+    }
+}
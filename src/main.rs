@@ -1,32 +1,83 @@
 use std::time::Duration;
 
-use crate::config::Config;
-use crate::health::run_coordinator;
-use color_eyre::{Result, eyre::eyre};
-use tracing::{Level, debug, error, info, warn};
-use tracing_subscriber::FmtSubscriber;
-
-mod cache;
-mod calculator;
-mod collector;
-mod config;
-mod db;
-mod health;
-mod mqtt;
-
-#[cfg(test)]
-mod test;
+use clap::{Parser, Subcommand};
+use color_eyre::Result;
+use pv_api::Config;
+use pv_api::health::{run_coordinator, run_once};
+use pv_api::logging::setup_logging_env;
+use pv_api::{bench, preflight, tui};
+use tracing::{debug, error, info, warn};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "FENECON PV data collector for MQTT and PostgreSQL")]
+struct Cli {
+    /// Run a single collection cycle and exit instead of looping
+    #[arg(long)]
+    once: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Store synthetic records to the cache and sync them to PostgreSQL.
+    /// Does not contact the inverter or MQTT.
+    Bench {
+        /// Number of synthetic records to generate
+        #[arg(long, default_value_t = 1000)]
+        records: usize,
+        /// Scratch PostgreSQL database for the sync, must differ from the
+        /// configured one. Without it only the cache is benchmarked.
+        #[arg(long)]
+        database_url: Option<String>,
+    },
+    /// Validate the configuration and try the inverter, PostgreSQL and MQTT
+    /// once. Exits nonzero if anything fails, stores and publishes nothing.
+    CheckConfig,
+    /// Run the collector with a live terminal dashboard instead of the HTTP
+    /// status server. Press q to quit.
+    Tui {
+        /// Log output goes here, it would draw over the dashboard otherwise
+        #[arg(long, default_value = "data/tui.log")]
+        log_file: String,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    setup()?;
+    let cli = Cli::parse();
+    let log_file = match &cli.command {
+        Some(Command::Tui { log_file }) => Some(log_file.as_str()),
+        _ => None,
+    };
+    setup(log_file)?;
 
-    run_coordinator().await?;
+    if let Some(Command::Bench {
+        records,
+        database_url,
+    }) = cli.command
+    {
+        let report = bench::run_bench(records, database_url).await?;
+        println!("{report}");
+    } else if let Some(Command::CheckConfig) = cli.command {
+        let report = preflight::run_preflight(&Config::new()?).await;
+        println!("{report}");
+        if !report.passed() {
+            std::process::exit(1);
+        }
+    } else if let Some(Command::Tui { .. }) = cli.command {
+        tui::run_tui().await?;
+    } else if cli.once {
+        run_once().await?;
+    } else {
+        run_coordinator().await?;
+    }
 
     return Ok(());
 }
 
-fn setup() -> Result<()> {
+fn setup(log_file: Option<&str>) -> Result<()> {
     if std::env::var("RUST_LIB_BACKTRACE").is_err() {
         unsafe { std::env::set_var("RUST_LIB_BACKTRACE", "0") }
     }
@@ -39,18 +90,7 @@ fn setup() -> Result<()> {
         unsafe { std::env::set_var("RUST_LOG", "info") }
     }
 
-    setup_logging_env();
+    setup_logging_env(log_file)?;
 
     Ok(())
 }
-
-fn setup_logging_env() {
-    FmtSubscriber::builder()
-        .with_max_level(Level::DEBUG)
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_file(true)
-        .with_line_number(true)
-        .pretty()
-        .init();
-}
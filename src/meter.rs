@@ -0,0 +1,85 @@
+use crate::config::ExternalMeterConfig;
+use color_eyre::eyre::{Result, eyre};
+use serde_json::Value;
+use std::time::Duration;
+use tracing::debug;
+
+/// Separate whole-house meter (Shelly EM, Tasmota, ...) polled over HTTP
+/// every cycle. The power is read from the JSON status at `json_path`.
+#[derive(Debug, Clone)]
+pub struct ExternalMeter {
+    config: ExternalMeterConfig,
+    client: reqwest::Client,
+}
+
+impl ExternalMeter {
+    /// None without a configured `url`.
+    pub fn new(config: &ExternalMeterConfig) -> Result<Option<Self>> {
+        if config.url.is_none() {
+            return Ok(None);
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()?;
+        Ok(Some(Self {
+            config: config.clone(),
+            client,
+        }))
+    }
+
+    /// Current consumption in W. Negative readings (meter mounted the
+    /// wrong way round, export) count as 0.
+    pub async fn read_power(&self) -> Result<u16> {
+        let url = self.config.url.as_deref().unwrap_or_default();
+        let status: Value = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        debug!(%status, "External meter status");
+
+        let power = lookup(&status, &self.config.json_path)
+            .and_then(Value::as_f64)
+            .ok_or_else(|| {
+                eyre!(
+                    "No number at '{}' in the meter status",
+                    self.config.json_path
+                )
+            })?;
+        Ok(power.round().clamp(0.0, u16::MAX as f64) as u16)
+    }
+}
+
+/// Dot separated path into `value`, numeric segments index arrays, e.g.
+/// `emeters.0.power`.
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |value, segment| match value {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => value.get(segment),
+        })
+}
+
+#[test]
+fn test_lookup_json_path() {
+    let status = serde_json::json!({
+        "emeters": [{ "power": 1234.6 }, { "power": 12.0 }],
+        "total_act_power": 880.2
+    });
+
+    assert_eq!(
+        lookup(&status, "emeters.0.power"),
+        Some(&serde_json::json!(1234.6))
+    );
+    assert_eq!(
+        lookup(&status, "total_act_power"),
+        Some(&serde_json::json!(880.2))
+    );
+    assert_eq!(lookup(&status, "emeters.2.power"), None);
+    assert_eq!(lookup(&status, "emeters.first.power"), None);
+}
@@ -0,0 +1,290 @@
+use crate::calculator::{ProcessedData, SensorValue};
+use prometheus::proto::MetricFamily;
+use prometheus::{
+    CounterVec, Encoder, HistogramOpts, HistogramVec, IntCounter, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Prometheus metrics updated by the coordinator every cycle and served on
+/// `/metrics`. Clones share the same underlying values.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    pv_production: IntGauge,
+    consumption: IntGauge,
+    grid_power: IntGauge,
+    battery_power: IntGauge,
+    battery_percent: IntGauge,
+    collection_failures: IntCounter,
+    mqtt_publish_failures: IntCounter,
+    mqtt_buffer_drops: IntCounter,
+    cache_records: IntCounter,
+    counter_resets: IntCounter,
+    seconds_in_state: CounterVec,
+    db_write_duration: HistogramVec,
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::with_registry(Registry::new())
+    }
+
+    /// Metrics of one inverter, every series carries its `device_id` label
+    /// so several inverters can be served side by side.
+    pub fn for_device(device_id: &str) -> Self {
+        let labels = HashMap::from([("device_id".to_string(), device_id.to_string())]);
+        Self::with_registry(
+            Registry::new_custom(None, Some(labels)).expect("valid device_id label"),
+        )
+    }
+
+    fn with_registry(registry: Registry) -> Self {
+        let gauge = |name: &str, help: &str| {
+            let gauge = IntGauge::new(name, help).expect("valid gauge definition");
+            registry
+                .register(Box::new(gauge.clone()))
+                .expect("gauge registered once");
+            gauge
+        };
+        let pv_production = gauge("pv_production_watts", "Current PV production in W");
+        let consumption = gauge("consumption_watts", "Current consumption in W");
+        let grid_power = gauge(
+            "grid_power_watts",
+            "Grid power in W, negative = export, positive = import",
+        );
+        let battery_power = gauge(
+            "battery_power_watts",
+            "Battery power in W, negative = charging, positive = discharging",
+        );
+        let battery_percent = gauge("battery_percent", "Battery state of charge in %");
+
+        let counter = |name: &str, help: &str| {
+            let counter = IntCounter::new(name, help).expect("valid counter definition");
+            registry
+                .register(Box::new(counter.clone()))
+                .expect("counter registered once");
+            counter
+        };
+        let collection_failures = counter(
+            "collection_failures_total",
+            "Inverter collections that failed after all retries",
+        );
+        let mqtt_publish_failures = counter(
+            "mqtt_publish_failures_total",
+            "Failed MQTT publishes of the power data",
+        );
+        let mqtt_buffer_drops = counter(
+            "mqtt_buffer_dropped_total",
+            "MQTT payloads dropped because the offline buffer was full",
+        );
+        let cache_records = counter("cache_records_total", "Rows written to the SQLite cache");
+        let counter_resets = counter(
+            "energy_counter_resets_total",
+            "Cycles where an inverter energy counter went backwards",
+        );
+
+        let seconds_in_state = CounterVec::new(
+            Opts::new(
+                "seconds_in_state",
+                "Time spent in each coordinator health state",
+            ),
+            &["state"],
+        )
+        .expect("valid counter definition");
+        registry
+            .register(Box::new(seconds_in_state.clone()))
+            .expect("counter registered once");
+
+        let db_write_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "db_write_duration_ms",
+                "Duration of PostgreSQL and SQLite cache writes in ms",
+            )
+            .buckets(vec![
+                1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0,
+            ]),
+            &["table"],
+        )
+        .expect("valid histogram definition");
+        registry
+            .register(Box::new(db_write_duration.clone()))
+            .expect("histogram registered once");
+
+        Self {
+            registry,
+            pv_production,
+            consumption,
+            grid_power,
+            battery_power,
+            battery_percent,
+            collection_failures,
+            mqtt_publish_failures,
+            mqtt_buffer_drops,
+            cache_records,
+            counter_resets,
+            seconds_in_state,
+            db_write_duration,
+        }
+    }
+
+    pub fn observe(&self, data: &ProcessedData) {
+        self.pv_production.set(data.full_production as i64);
+        self.consumption.set(data.consumption as i64);
+        self.grid_power.set(data.supply_state.power_value() as i64);
+        self.battery_power
+            .set(data.battery_status.battery_state.power_value() as i64);
+        self.battery_percent
+            .set(data.battery_status.battery_percent as i64);
+    }
+
+    pub fn record_collection_failure(&self) {
+        self.collection_failures.inc();
+    }
+
+    pub fn record_mqtt_publish_failure(&self) {
+        self.mqtt_publish_failures.inc();
+    }
+
+    /// Catches the counter up with the drop total kept by the MQTT client.
+    pub fn record_mqtt_buffer_drops(&self, total: u64) {
+        let recorded = self.mqtt_buffer_drops.get();
+        if total > recorded {
+            self.mqtt_buffer_drops.inc_by(total - recorded);
+        }
+    }
+
+    pub fn record_cached(&self, rows: u64) {
+        self.cache_records.inc_by(rows);
+    }
+
+    pub fn record_counter_reset(&self) {
+        self.counter_resets.inc();
+    }
+
+    pub fn record_state_time(&self, state: &str, seconds: f64) {
+        if seconds > 0.0 {
+            self.seconds_in_state
+                .with_label_values(&[state])
+                .inc_by(seconds);
+        }
+    }
+
+    /// One successful write to `table`. The histogram buckets are atomics,
+    /// concurrent writers do not block each other.
+    pub fn record_db_write(&self, table: &str, duration: Duration) {
+        self.db_write_duration
+            .with_label_values(&[table])
+            .observe(duration.as_secs_f64() * 1000.0);
+    }
+
+    /// Writes to `table` recorded so far.
+    pub fn db_write_samples(&self, table: &str) -> u64 {
+        self.db_write_duration
+            .with_label_values(&[table])
+            .get_sample_count()
+    }
+
+    /// Registered metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        Self::render_all([self])
+    }
+
+    /// Several metric sets in one exposition. Families of the same name are
+    /// merged, each metric gets one HELP/TYPE header with a series per set.
+    pub fn render_all<'a>(metrics: impl IntoIterator<Item = &'a Metrics>) -> String {
+        let mut families: Vec<MetricFamily> = Vec::new();
+        for mut family in metrics
+            .into_iter()
+            .flat_map(|metrics| metrics.registry.gather())
+        {
+            match families
+                .iter_mut()
+                .find(|known| known.name() == family.name())
+            {
+                Some(known) => known.mut_metric().extend(family.take_metric()),
+                None => families.push(family),
+            }
+        }
+
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&families, &mut buffer) {
+            tracing::error!(error = %e, "Failed to encode metrics");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_matches_cycle() {
+    use crate::calculator::{BatteryState, BatteryStatus, SupplyState};
+    use crate::server::{AppState, DeviceState, serve_on};
+
+    let metrics = Metrics::new();
+    let data = ProcessedData {
+        supply_state: SupplyState::Demand(420),
+        battery_status: BatteryStatus {
+            battery_state: BatteryState::Discharging(750),
+            battery_percent: 58,
+            battery_energy: 5800.0,
+            battery_temperature: None,
+        },
+        full_production: 1300,
+        consumption: 2470,
+        ..Default::default()
+    };
+
+    // One simulated cycle
+    metrics.observe(&data);
+    metrics.record_cached(2);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve_on(
+        listener,
+        AppState {
+            devices: vec![DeviceState {
+                metrics: metrics.clone(),
+                ..Default::default()
+            }],
+        },
+    ));
+
+    let body = reqwest::get(format!("http://{}/metrics", addr))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let value = |name: &str| -> i64 {
+        body.lines()
+            .find_map(|line| line.strip_prefix(&format!("{} ", name)))
+            .unwrap_or_else(|| panic!("metric {} missing", name))
+            .parse()
+            .unwrap()
+    };
+
+    assert_eq!(value("pv_production_watts"), 1300);
+    assert_eq!(value("consumption_watts"), 2470);
+    assert_eq!(value("grid_power_watts"), 420);
+    assert_eq!(value("battery_power_watts"), 750);
+    assert_eq!(value("battery_percent"), 58);
+    assert_eq!(value("cache_records_total"), 2);
+    assert_eq!(value("collection_failures_total"), 0);
+    assert_eq!(value("mqtt_publish_failures_total"), 0);
+}
@@ -0,0 +1,73 @@
+use crate::calculator::ProcessedData;
+use crate::collector::RawPVData;
+use crate::config::Config;
+use color_eyre::eyre::{Result, eyre};
+use std::sync::Mutex;
+
+/// Reads and processes the inverter without MQTT, database or coordinator,
+/// for embedding the collector in another application. The caller decides
+/// when to poll, the last reading stays available through [`latest`].
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> color_eyre::Result<()> {
+/// use pv_api::{Config, PvMonitor};
+///
+/// // Recorded inverter responses instead of a real device
+/// let config = Config {
+///     pv_baseaddress: "file://fixtures/".to_string(),
+///     ..Config::default()
+/// };
+/// let monitor = PvMonitor::new(config)?;
+/// assert!(monitor.latest().is_none());
+///
+/// let data = monitor.poll_once().await?;
+/// assert_eq!(data.consumption, 1100);
+/// assert_eq!(monitor.latest().map(|latest| latest.consumption), Some(1100));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`latest`]: PvMonitor::latest
+#[derive(Debug)]
+pub struct PvMonitor {
+    config: Config,
+    latest: Mutex<Option<ProcessedData>>,
+}
+
+impl PvMonitor {
+    pub fn new(config: Config) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            latest: Mutex::new(None),
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// One reading from the inverter. Implausible readings are an error and
+    /// leave `latest` untouched, like the coordinator skips them.
+    pub async fn poll_once(&self) -> Result<ProcessedData> {
+        let config = &self.config;
+        let raw_data = RawPVData::fill_raw(
+            &config.pv_baseaddress,
+            &config.collected_channels(),
+            &config.pv_auth,
+        )
+        .await?;
+        if let Err(problems) = raw_data.validate(&config.plausibility_limits) {
+            return Err(eyre!("Implausible reading: {}", problems.join("; ")));
+        }
+
+        let data = ProcessedData::process_raw(raw_data, &config.battery_config);
+        *self.latest.lock().unwrap() = Some(data.clone());
+        Ok(data)
+    }
+
+    pub fn latest(&self) -> Option<ProcessedData> {
+        self.latest.lock().unwrap().clone()
+    }
+}
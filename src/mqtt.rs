@@ -1,13 +1,35 @@
 use crate::calculator::{DataHistory, MqttPayload, ProcessedData, SensorValue};
-use crate::config::MqttConfig;
+use crate::changes::{FieldChange, changes_payload};
+use crate::config::{MqttConfig, TopicType};
+use crate::gap::DataGap;
+use crate::outage::GridEvent;
+use crate::snapshot::Snapshot;
 use color_eyre::eyre::Error;
+use color_eyre::eyre::{WrapErr, eyre};
 use color_eyre::{Report, Result};
-use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rumqttc::{AsyncClient, ClientError, Event, EventLoop, MqttOptions, Packet, QoS, Transport};
+use serde::Serialize;
 use serde_json::json;
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError};
 use std::time::Duration;
-use tokio::sync::Mutex;
-use tracing::{debug, error, info, warn};
+use tokio::sync::{Mutex, Notify, RwLock, mpsc, watch};
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{Instrument, debug, error, info, warn};
+
+/// Tries per discovery config before it counts as failed
+const DISCOVERY_ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubled for every further one
+const DISCOVERY_RETRY_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(2);
+/// Every reconnect delay is scaled by a random factor in 1 +/- this
+const RECONNECT_JITTER: f64 = 0.2;
+pub const REFRESH_PAYLOAD: &str = "refresh";
+/// Sent to the refresh topic to move the SQLite cache to Postgres right away
+pub const SYNC_PAYLOAD: &str = "sync";
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum MQTTHealthStatus {
@@ -23,6 +45,7 @@ pub struct MQTTState {
     pub last_successful_publish: Option<std::time::Instant>,
     pub failed_publish_count: u32,
     pub last_error: Option<String>,
+    pub eventloop_restarts: u32,
 }
 
 impl Default for MQTTState {
@@ -32,108 +55,685 @@ impl Default for MQTTState {
             last_successful_publish: None,
             failed_publish_count: 0,
             last_error: None,
+            eventloop_restarts: 0,
         }
     }
 }
 
+/// A publish received on one of our subscriptions, forwarded from the event loop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncomingMessage {
+    pub topic: String,
+    pub payload: String,
+}
+
+/// One Home Assistant entity. `config` is the full per-entity discovery
+/// config, in `device` mode the shared device blocks are stripped from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveryComponent {
+    pub platform: &'static str,
+    pub object_id: String,
+    pub config: serde_json::Value,
+}
+
+/// Coordinator health, published every cycle to the diagnostics topic.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DiagnosticsSnapshot {
+    pub state: String,
+    pub postgres_health: String,
+    pub mqtt_health: String,
+    pub db_consecutive_failures: u32,
+    /// Power and energy rows waiting in the SQLite cache
+    pub cache_records: u64,
+    /// The inverter kept returning the same reading, see `StaleDetector`
+    pub data_stale: bool,
+}
+
+impl DiagnosticsSnapshot {
+    pub fn payload(&self) -> serde_json::Value {
+        let mut payload = json!(self);
+        payload["timestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        payload
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SolarMqttClient {
-    pub client: AsyncClient,
+    client: Arc<RwLock<AsyncClient>>,
     device_id: String,
     state: Arc<Mutex<MQTTState>>,
     config: MqttConfig,
+    incoming: Arc<Mutex<mpsc::UnboundedReceiver<IncomingMessage>>>,
+    subscriptions: Arc<Mutex<Vec<String>>>,
+    eventloop_abort: Arc<std::sync::Mutex<AbortHandle>>,
+    /// Number of PUBACKs received, used to confirm discovery publishes
+    pub_acks: Arc<watch::Sender<u64>>,
+    discovery_published: Arc<AtomicUsize>,
+    /// Discovery has to be sent again, see `discovery_pending`
+    discovery_pending: Arc<AtomicBool>,
+    /// Signalled by the event loop when a refresh command arrives. Holds at
+    /// most one permit, so refreshes requested during a cycle coalesce.
+    refresh: Arc<Notify>,
+    /// Publishes are logged instead of sent. Shared with the event loop,
+    /// which republishes availability on its own.
+    dry_run: Arc<AtomicBool>,
+    /// Set between ConnAck and the next connection error
+    connected: Arc<AtomicBool>,
+    offline: Arc<OfflineBuffer>,
+}
+
+/// Discovery configs that were not published after all retries, by
+/// object id.
+#[derive(Debug)]
+pub struct DiscoveryError {
+    pub failed: Vec<String>,
+}
+
+impl std::fmt::Display for DiscoveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Discovery configs not published: {}",
+            self.failed.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for DiscoveryError {}
+
+/// A data payload held back while the broker was unreachable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferedPublish {
+    pub topic: String,
+    pub payload: String,
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+/// Power, state and history payloads that could not be published while
+/// disconnected, flushed in order on the next ConnAck. When full the oldest
+/// payload is dropped.
+#[derive(Debug)]
+pub struct OfflineBuffer {
+    capacity: usize,
+    messages: std::sync::Mutex<VecDeque<BufferedPublish>>,
+    dropped: AtomicU64,
+    /// Keeps concurrent flushes from reordering the payloads
+    flushing: Mutex<()>,
+}
+
+impl OfflineBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            messages: std::sync::Mutex::new(VecDeque::with_capacity(capacity)),
+            dropped: AtomicU64::new(0),
+            flushing: Mutex::new(()),
+        }
+    }
+
+    pub fn push(&self, topic: &str, payload: String, qos: QoS, retain: bool) {
+        if self.capacity == 0 {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() >= self.capacity {
+            messages.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        messages.push_back(BufferedPublish {
+            topic: topic.to_string(),
+            payload,
+            qos,
+            retain,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Payloads lost to a full buffer since startup.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Publishes the buffered payloads oldest first. Stops at the first
+    /// failure and keeps that payload and everything after it.
+    pub async fn flush(&self, client: &AsyncClient) {
+        let _flushing = self.flushing.lock().await;
+        let mut flushed = 0usize;
+
+        loop {
+            let Some(message) = self.messages.lock().unwrap().pop_front() else {
+                break;
+            };
+            if let Err(e) = client
+                .publish(
+                    &message.topic,
+                    message.qos,
+                    message.retain,
+                    message.payload.clone(),
+                )
+                .await
+            {
+                warn!(error = %e, "Failed to flush buffered MQTT publish");
+                self.messages.lock().unwrap().push_front(message);
+                break;
+            }
+            flushed += 1;
+        }
+
+        if flushed > 0 {
+            info!(
+                flushed,
+                "Flushed MQTT publishes buffered while disconnected"
+            );
+        }
+    }
+}
+
+/// Delay between reconnect attempts: base * 2^n capped at `max`, then
+/// scaled by a random +/- 20%, so instances sharing a broker do not all
+/// reconnect at the same moment after an outage.
+#[derive(Debug, Clone)]
+pub struct ReconnectBackoff {
+    base: Duration,
+    max: Duration,
+    attempts: u32,
+    rng: StdRng,
+}
+
+impl ReconnectBackoff {
+    pub fn new(base: Duration, max: Duration, seed: u64) -> Self {
+        Self {
+            base,
+            max: max.max(base),
+            attempts: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn for_config(config: &MqttConfig) -> Self {
+        Self::new(
+            RECONNECT_BASE_DELAY,
+            Duration::from_secs(config.reconnect_max_secs),
+            rand::random(),
+        )
+    }
+
+    /// Unjittered delay for the current attempt.
+    pub fn nominal_delay(&self) -> Duration {
+        let factor = 2_u32.saturating_pow(self.attempts);
+        self.base.saturating_mul(factor).min(self.max)
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let nominal = self.nominal_delay();
+        self.attempts = self.attempts.saturating_add(1);
+        let jitter = self
+            .rng
+            .random_range(1.0 - RECONNECT_JITTER..=1.0 + RECONNECT_JITTER);
+        nominal.mul_f64(jitter)
+    }
+
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+/// What the event loop does on a ConnAck. Uses the client paired with the
+/// event loop and never waits on it inline, the event loop is the one
+/// draining its queue.
+#[derive(Debug, Clone)]
+struct ConnectionHooks {
+    client: AsyncClient,
+    availability_topic: String,
+    /// QoS and retain flag of the availability topic
+    availability_policy: (QoS, bool),
+    dry_run: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+    offline: Arc<OfflineBuffer>,
+}
+
+/// Where incoming publishes are routed by the event loop
+struct IncomingRoutes {
+    incoming_tx: mpsc::UnboundedSender<IncomingMessage>,
+    refresh_topic: String,
+    refresh: Arc<Notify>,
+}
+
+impl ConnectionHooks {
+    /// After a reconnect the broker may have published our last will, so the
+    /// retained `online` is sent again.
+    fn republish_availability(&self) {
+        if self.dry_run.load(Ordering::SeqCst) {
+            info!(topic = %self.availability_topic, "Dry run, not republishing availability");
+            return;
+        }
+
+        let (qos, retain) = self.availability_policy;
+        match self
+            .client
+            .try_publish(&self.availability_topic, qos, retain, "online")
+        {
+            Ok(()) => info!("Republished availability after MQTT reconnect"),
+            Err(e) => warn!(error = %e, "Failed to republish availability after reconnect"),
+        }
+    }
+
+    fn flush_offline(&self) {
+        if self.offline.is_empty() {
+            return;
+        }
+
+        let offline = self.offline.clone();
+        let client = self.client.clone();
+        tokio::spawn(async move { offline.flush(&client).await }.in_current_span());
+    }
+}
+
+async fn run_eventloop(
+    mut eventloop: EventLoop,
+    hooks: ConnectionHooks,
+    mut backoff: ReconnectBackoff,
+    state: Arc<Mutex<MQTTState>>,
+    routes: IncomingRoutes,
+    pub_acks: Arc<watch::Sender<u64>>,
+) {
+    let IncomingRoutes {
+        incoming_tx,
+        refresh_topic,
+        refresh,
+    } = routes;
+    let mut consecutive_errors = 0u32;
+    // The first connection is announced by whoever created the client
+    let mut connected_before = false;
+
+    loop {
+        match eventloop.poll().await {
+            Ok(notification) => {
+                consecutive_errors = 0;
+
+                match notification {
+                    Event::Incoming(Packet::ConnAck(_)) => {
+                        info!("MQTT connected successfully");
+                        backoff.reset();
+                        hooks.connected.store(true, Ordering::SeqCst);
+                        if std::mem::replace(&mut connected_before, true) {
+                            hooks.republish_availability();
+                        }
+                        hooks.flush_offline();
+                        let mut state_guard = state.lock().await;
+                        state_guard.status = MQTTHealthStatus::Healthy;
+                        state_guard.last_error = None;
+                        drop(state_guard);
+                    }
+                    Event::Incoming(Packet::PubAck(_)) => {
+                        debug!("Received publish ACK");
+                        pub_acks.send_modify(|count| *count += 1);
+                        let mut state_guard = state.lock().await;
+                        state_guard.last_successful_publish = Some(std::time::Instant::now());
+                        if state_guard.failed_publish_count > 0 {
+                            state_guard.failed_publish_count = 0;
+                            state_guard.status = MQTTHealthStatus::Healthy;
+                        }
+                        drop(state_guard);
+                    }
+                    Event::Incoming(Packet::Publish(publish)) => {
+                        // Handled right here so the coordinator wakes up mid-sleep
+                        if publish.topic == refresh_topic
+                            && publish.payload.trim_ascii() == REFRESH_PAYLOAD.as_bytes()
+                        {
+                            info!("Refresh requested via MQTT");
+                            refresh.notify_one();
+                            continue;
+                        }
+
+                        let message = IncomingMessage {
+                            topic: publish.topic.clone(),
+                            payload: String::from_utf8_lossy(&publish.payload).to_string(),
+                        };
+                        debug!(topic = %message.topic, "Received MQTT message");
+                        if incoming_tx.send(message).is_err() {
+                            debug!("No receiver for incoming MQTT messages");
+                        }
+
+                        // The sync runs with the incoming commands, wake the loop for it
+                        if publish.topic == refresh_topic
+                            && publish.payload.trim_ascii() == SYNC_PAYLOAD.as_bytes()
+                        {
+                            info!("Cache sync requested via MQTT");
+                            refresh.notify_one();
+                        }
+                    }
+                    Event::Incoming(Packet::Disconnect) => {
+                        warn!("MQTT disconnected");
+                        hooks.connected.store(false, Ordering::SeqCst);
+                        let mut state_guard = state.lock().await;
+                        state_guard.status = MQTTHealthStatus::Unhealthy;
+                        state_guard.last_error = Some("MQTT Disconnected".to_string());
+                        drop(state_guard);
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                consecutive_errors += 1;
+                hooks.connected.store(false, Ordering::SeqCst);
+                error!(error = %e, consecutive_errors, "MQTT connection error");
+
+                let mut state_guard = state.lock().await;
+                state_guard.status = if consecutive_errors >= 3 {
+                    MQTTHealthStatus::Unhealthy
+                } else {
+                    MQTTHealthStatus::Degraded
+                };
+                state_guard.last_error = Some(format!("Connection error: {}", e));
+                drop(state_guard);
+
+                let delay = backoff.next_delay();
+                debug!(
+                    delay_ms = delay.as_millis() as u64,
+                    "Waiting before MQTT reconnect"
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
 }
 
 impl SolarMqttClient {
     pub async fn new(mqtt_config: &MqttConfig, device_id: String) -> Result<Self> {
+        let mqttoptions = Self::mqtt_options(mqtt_config, &device_id)?;
+        let (client, eventloop) = AsyncClient::new(mqttoptions.clone(), 10);
+
+        let state = Arc::new(Mutex::new(MQTTState::default()));
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
+
+        let pub_acks = Arc::new(watch::Sender::new(0));
+        let refresh = Arc::new(Notify::new());
+        let refresh_topic = mqtt_config.get_state_topic(&device_id, "command");
+        let dry_run = Arc::new(AtomicBool::new(false));
+        let connected = Arc::new(AtomicBool::new(false));
+        let offline = Arc::new(OfflineBuffer::new(mqtt_config.offline_buffer_size));
+
+        let handle = tokio::spawn(
+            run_eventloop(
+                eventloop,
+                ConnectionHooks {
+                    client: client.clone(),
+                    availability_topic: mqtt_config.get_availability_topic(&device_id),
+                    availability_policy: mqtt_config.policy(TopicType::Availability),
+                    dry_run: dry_run.clone(),
+                    connected: connected.clone(),
+                    offline: offline.clone(),
+                },
+                ReconnectBackoff::for_config(mqtt_config),
+                state.clone(),
+                IncomingRoutes {
+                    incoming_tx: incoming_tx.clone(),
+                    refresh_topic,
+                    refresh: refresh.clone(),
+                },
+                pub_acks.clone(),
+            )
+            .in_current_span(),
+        );
+
+        let mqtt_client = Self {
+            client: Arc::new(RwLock::new(client)),
+            device_id,
+            state,
+            config: mqtt_config.clone(),
+            incoming: Arc::new(Mutex::new(incoming_rx)),
+            subscriptions: Arc::new(Mutex::new(Vec::new())),
+            eventloop_abort: Arc::new(std::sync::Mutex::new(handle.abort_handle())),
+            pub_acks,
+            discovery_published: Arc::new(AtomicUsize::new(0)),
+            discovery_pending: Arc::new(AtomicBool::new(false)),
+            refresh,
+            dry_run,
+            connected,
+            offline,
+        };
+
+        if mqtt_config.eventloop_supervisor {
+            tokio::spawn(
+                mqtt_client
+                    .clone()
+                    .supervise_eventloop(handle, mqttoptions, incoming_tx)
+                    .in_current_span(),
+            );
+        }
+
+        Ok(mqtt_client)
+    }
+
+    fn mqtt_options(mqtt_config: &MqttConfig, device_id: &str) -> Result<MqttOptions> {
         let client_id = format!("{}_{}", mqtt_config.client_id_prefix, device_id);
-        let mut mqttoptions = MqttOptions::new(client_id, &mqtt_config.broker_url, 1883);
+        let mut mqttoptions =
+            MqttOptions::new(client_id, &mqtt_config.broker_url, mqtt_config.mqtt_port);
         mqttoptions.set_keep_alive(Duration::from_secs(mqtt_config.keep_alive_secs));
 
         if !mqtt_config.username.is_empty() {
             mqttoptions.set_credentials(&mqtt_config.username, &mqtt_config.password);
         }
 
+        if mqtt_config.use_tls {
+            mqttoptions.set_transport(Self::tls_transport(mqtt_config)?);
+        }
+
         // Set Last Will and Testament
+        let (qos, retain) = mqtt_config.policy(TopicType::Availability);
         mqttoptions.set_last_will(rumqttc::LastWill::new(
             &mqtt_config.last_will_topic,
             mqtt_config.last_will_payload.clone(),
-            mqtt_config.to_qos(),
-            true,
+            qos,
+            retain,
         ));
 
-        let (client, mut eventloop) = AsyncClient::new(mqttoptions, 10);
+        Ok(mqttoptions)
+    }
 
-        let state = Arc::new(Mutex::new(MQTTState::default()));
-        let state_for_eventloop = state.clone();
-        let config_for_eventloop = mqtt_config.clone();
-        let device_id_for_eventloop = device_id.clone();
-
-        tokio::spawn(async move {
-            let mut consecutive_errors = 0u32;
-
-            loop {
-                match eventloop.poll().await {
-                    Ok(notification) => {
-                        consecutive_errors = 0;
-
-                        match notification {
-                            Event::Incoming(Packet::ConnAck(_)) => {
-                                info!("MQTT connected successfully");
-                                let mut state_guard = state_for_eventloop.lock().await;
-                                state_guard.status = MQTTHealthStatus::Healthy;
-                                state_guard.last_error = None;
-                                drop(state_guard);
-                            }
-                            Event::Incoming(Packet::PubAck(_)) => {
-                                debug!("Received publish ACK");
-                                let mut state_guard = state_for_eventloop.lock().await;
-                                state_guard.last_successful_publish =
-                                    Some(std::time::Instant::now());
-                                if state_guard.failed_publish_count > 0 {
-                                    state_guard.failed_publish_count = 0;
-                                    state_guard.status = MQTTHealthStatus::Healthy;
-                                }
-                                drop(state_guard);
-                            }
-                            Event::Incoming(Packet::Disconnect) => {
-                                warn!("MQTT disconnected");
-                                let mut state_guard = state_for_eventloop.lock().await;
-                                state_guard.status = MQTTHealthStatus::Unhealthy;
-                                state_guard.last_error = Some("MQTT Disconnected".to_string());
-                                drop(state_guard);
-                            }
-                            _ => {}
-                        }
-                    }
-                    Err(e) => {
-                        consecutive_errors += 1;
-                        error!(error = %e, consecutive_errors, "MQTT connection error");
-
-                        let mut state_guard = state_for_eventloop.lock().await;
-                        state_guard.status = if consecutive_errors >= 3 {
-                            MQTTHealthStatus::Unhealthy
-                        } else {
-                            MQTTHealthStatus::Degraded
-                        };
-                        state_guard.last_error = Some(format!("Connection error: {}", e));
-                        drop(state_guard);
+    /// Connects once under its own client id and disconnects cleanly, so
+    /// the broker does not send the last will. Nothing is published.
+    pub async fn check_connection(mqtt_config: &MqttConfig, timeout: Duration) -> Result<()> {
+        let options = Self::mqtt_options(mqtt_config, "preflight")?;
+        let (client, mut eventloop) = AsyncClient::new(options, 10);
 
-                        let delay = std::cmp::min(consecutive_errors * 2, 30);
-                        tokio::time::sleep(Duration::from_secs(delay as u64)).await;
-                    }
+        tokio::time::timeout(timeout, async {
+            while !matches!(eventloop.poll().await?, Event::Incoming(Packet::ConnAck(_))) {}
+
+            client.disconnect().await?;
+            while let Ok(event) = eventloop.poll().await {
+                if matches!(event, Event::Outgoing(rumqttc::Outgoing::Disconnect)) {
+                    break;
                 }
             }
-        });
+            Ok(())
+        })
+        .await
+        .map_err(|_| eyre!("No ConnAck within {:?}", timeout))?
+    }
 
-        let mqtt_client = Self {
-            client,
-            device_id,
-            state,
-            config: mqtt_config.clone(),
+    /// Without a CA certificate the platform trust store is used.
+    fn tls_transport(mqtt_config: &MqttConfig) -> Result<Transport> {
+        let read = |path: &String| {
+            std::fs::read(path).wrap_err_with(|| format!("Failed to read MQTT TLS file {}", path))
         };
 
-        Ok(mqtt_client)
+        let Some(ca_cert_path) = &mqtt_config.ca_cert_path else {
+            return Ok(Transport::tls_with_default_config());
+        };
+
+        let client_auth = match (&mqtt_config.client_cert_path, &mqtt_config.client_key_path) {
+            (Some(cert_path), Some(key_path)) => Some((read(cert_path)?, read(key_path)?)),
+            (None, None) => None,
+            _ => {
+                return Err(eyre!(
+                    "MQTT client certificate and key must be configured together"
+                ));
+            }
+        };
+
+        Ok(Transport::tls(read(ca_cert_path)?, client_auth, None))
+    }
+
+    /// Waits on the event loop task and replaces it with a fresh connection
+    /// whenever it ends, e.g. after a panic inside the MQTT library.
+    async fn supervise_eventloop(
+        self,
+        mut handle: JoinHandle<()>,
+        mqttoptions: MqttOptions,
+        incoming_tx: mpsc::UnboundedSender<IncomingMessage>,
+    ) {
+        loop {
+            let reason = match handle.await {
+                Ok(()) => "exited".to_string(),
+                Err(e) if e.is_panic() => "panicked".to_string(),
+                Err(_) => "was aborted".to_string(),
+            };
+
+            let restarts = {
+                let mut state_guard = self.state.lock().await;
+                state_guard.status = MQTTHealthStatus::Unhealthy;
+                state_guard.last_error = Some(format!("MQTT event loop {}", reason));
+                state_guard.eventloop_restarts += 1;
+                state_guard.eventloop_restarts
+            };
+            self.connected.store(false, Ordering::SeqCst);
+            error!(restarts, "MQTT event loop {}, respawning", reason);
+
+            let delay = std::cmp::min(restarts * 2, 30);
+            tokio::time::sleep(Duration::from_secs(delay as u64)).await;
+
+            let (client, eventloop) = AsyncClient::new(mqttoptions.clone(), 10);
+            handle = tokio::spawn(
+                run_eventloop(
+                    eventloop,
+                    ConnectionHooks {
+                        client: client.clone(),
+                        availability_topic: self.config.get_availability_topic(&self.device_id),
+                        availability_policy: self.config.policy(TopicType::Availability),
+                        dry_run: self.dry_run.clone(),
+                        connected: self.connected.clone(),
+                        offline: self.offline.clone(),
+                    },
+                    ReconnectBackoff::for_config(&self.config),
+                    self.state.clone(),
+                    IncomingRoutes {
+                        incoming_tx: incoming_tx.clone(),
+                        refresh_topic: self.refresh_topic(),
+                        refresh: self.refresh.clone(),
+                    },
+                    self.pub_acks.clone(),
+                )
+                .in_current_span(),
+            );
+            *self
+                .eventloop_abort
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = handle.abort_handle();
+            *self.client.write().await = client;
+
+            // A new connection starts without our subscriptions
+            let topics = self.subscriptions.lock().await.clone();
+            for topic in topics {
+                if let Err(e) = self
+                    .client()
+                    .await
+                    .subscribe(&topic, self.config.to_qos())
+                    .await
+                {
+                    warn!(error = %e, topic = %topic, "Failed to resubscribe after respawn");
+                }
+            }
+            self.publish_availability(true).await;
+        }
+    }
+
+    /// Stops the current event loop task. With the supervisor enabled a fresh
+    /// one is spawned, otherwise MQTT stays down.
+    pub fn restart_eventloop(&self) {
+        self.eventloop_abort
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .abort();
+    }
+
+    pub fn with_dry_run(self, dry_run: bool) -> Self {
+        self.dry_run.store(dry_run, Ordering::SeqCst);
+        self
+    }
+
+    /// Every publish goes through here, in dry-run mode it only logs what
+    /// would have been sent.
+    async fn publish(
+        &self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: impl Into<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        let payload = payload.into();
+        if self.dry_run.load(Ordering::SeqCst) {
+            info!(
+                topic,
+                retain,
+                payload = %String::from_utf8_lossy(&payload),
+                "Dry run, not publishing"
+            );
+            return Ok(());
+        }
+
+        self.client()
+            .await
+            .publish(topic, qos, retain, payload)
+            .await
+    }
+
+    /// Data payloads go through here: while disconnected they are kept in the
+    /// offline buffer instead of being handed to the client.
+    async fn publish_or_buffer(
+        &self,
+        topic: &str,
+        topic_type: TopicType,
+        payload: String,
+    ) -> Result<()> {
+        let (qos, retain) = self.config.policy(topic_type);
+        if !self.dry_run.load(Ordering::SeqCst) && !self.is_connected() {
+            self.offline.push(topic, payload, qos, retain);
+            return Err(eyre!(
+                "MQTT not connected, payload buffered ({} waiting)",
+                self.offline.len()
+            ));
+        }
+
+        if let Err(e) = self.publish(topic, qos, retain, payload.clone()).await {
+            self.offline.push(topic, payload, qos, retain);
+            return Err(Report::new(e));
+        }
+        Ok(())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    pub fn offline_buffer(&self) -> &OfflineBuffer {
+        &self.offline
+    }
+
+    /// Handle to the current connection, replaced when the event loop is respawned.
+    pub async fn client(&self) -> AsyncClient {
+        self.client.read().await.clone()
     }
 
     pub async fn is_healthy(&self) -> bool {
@@ -155,16 +755,31 @@ impl SolarMqttClient {
     }
 
     pub async fn publish_current_data(&self, data: &ProcessedData) -> Result<()> {
+        self.publish_power_payload(data.to_state_json()).await
+    }
+
+    /// Publishes only the power fields that moved since `prev`, beyond
+    /// `power_deadband_w` for the W values. Home Assistant evaluates every
+    /// value_template on its own, sensors missing from the payload keep
+    /// their state. Nothing is sent when no field changed.
+    pub async fn publish_changed(&self, prev: &ProcessedData, cur: &ProcessedData) -> Result<()> {
+        let payload = cur.changed_state_json(prev, self.config.power_deadband_w);
+        if payload
+            .as_object()
+            .is_some_and(|fields| fields.keys().all(|key| key == "timestamp"))
+        {
+            debug!("No power field changed beyond the deadband, skipping publish");
+            return Ok(());
+        }
+
+        self.publish_power_payload(payload).await
+    }
+
+    async fn publish_power_payload(&self, payload: serde_json::Value) -> Result<()> {
         let topic = self.config.get_state_topic(&self.device_id, "power");
 
         match self
-            .client
-            .publish(
-                &topic,
-                self.config.to_qos(),
-                false,
-                data.to_state_json().to_string(),
-            )
+            .publish_or_buffer(&topic, TopicType::Power, payload.to_string())
             .await
         {
             Ok(_) => {
@@ -188,7 +803,7 @@ impl SolarMqttClient {
                     "Failed to publish power data"
                 );
                 drop(state_guard);
-                Err(Report::new(e))
+                Err(e)
             }
         }
     }
@@ -197,12 +812,11 @@ impl SolarMqttClient {
         let topic = self.config.get_state_topic(&self.device_id, "energy");
 
         match self
-            .client
-            .publish(
+            .publish_or_buffer(
                 &topic,
-                self.config.to_qos(),
-                false,
-                data.to_state_json().to_string(),
+                TopicType::Energy,
+                data.to_state_json_with_unit(self.config.energy_unit)
+                    .to_string(),
             )
             .await
         {
@@ -220,6 +834,32 @@ impl SolarMqttClient {
         }
     }
 
+    /// Republishes a snapshot restored from disk, flagged with `stale: true`.
+    pub async fn publish_stale_snapshot(&self, snapshot: &Snapshot) {
+        let power_topic = self.config.get_state_topic(&self.device_id, "power");
+        let energy_topic = self.config.get_state_topic(&self.device_id, "energy");
+
+        for (topic, topic_type, payload) in [
+            (power_topic, TopicType::Power, snapshot.stale_power_json()),
+            (
+                energy_topic,
+                TopicType::Energy,
+                snapshot.stale_energy_json(self.config.energy_unit),
+            ),
+        ] {
+            let (qos, retain) = self.config.policy(topic_type);
+            if let Err(e) = self.publish(&topic, qos, retain, payload.to_string()).await {
+                error!(error = %e, topic = %topic, "Failed to publish stale snapshot");
+                return;
+            }
+        }
+
+        info!(
+            collected_at = %snapshot.collected_at,
+            "Published restored snapshot as stale"
+        );
+    }
+
     pub async fn publish_state_data(&self, data: &ProcessedData) {
         let topic = self.config.get_state_topic(&self.device_id, "state");
 
@@ -231,8 +871,7 @@ impl SolarMqttClient {
         });
 
         match self
-            .client
-            .publish(&topic, self.config.to_qos(), false, state_json.to_string())
+            .publish_or_buffer(&topic, TopicType::State, state_json.to_string())
             .await
         {
             Ok(_) => {
@@ -249,139 +888,586 @@ impl SolarMqttClient {
         }
     }
 
-    pub async fn setup_discovery(&self) -> Result<()> {
-        info!("Setting up Home Assistant MQTT Discovery");
+    pub async fn publish_changes(&self, changes: &[FieldChange]) {
+        let topic = self.config.get_state_topic(&self.device_id, "changes");
+        let (qos, _) = self.config.policy(TopicType::State);
 
-        info!("Solar Energy Monitor starting - sending discovery messages");
+        // Events are never retained, a new subscriber must not replay them
+        match self
+            .publish(&topic, qos, false, changes_payload(changes).to_string())
+            .await
+        {
+            Ok(_) => {
+                debug!(changed_fields = changes.len(), "Published change events");
+            }
+            Err(e) => {
+                let mut state_guard = self.state.lock().await;
+                state_guard.last_error = Some(format!("Change publish error: {}", e));
 
-        self.create_sensor_config(
-            "pv_production",
-            "PV Production",
-            "power",
-            "W",
-            "measurement",
-            "{{ value_json.pv_production }}",
-        )
-        .await?;
+                error!(error = %e, "Failed to publish change events");
+                drop(state_guard);
+            }
+        }
+    }
 
-        self.create_sensor_config(
-            "consumption",
-            "Power Consumption",
-            "power",
-            "W",
-            "measurement",
-            "{{ value_json.consumption }}",
-        )
-        .await?;
+    pub async fn publish_grid_event(&self, event: &GridEvent) {
+        let topic = self.config.get_state_topic(&self.device_id, "grid_outage");
+        let (qos, _) = self.config.policy(TopicType::State);
 
-        self.create_sensor_config(
-            "supply_power",
-            "Grid Power",
-            "power",
-            "W",
-            "measurement",
-            "{{ value_json.supply_power }}",
-        )
-        .await?;
+        match self
+            .publish(&topic, qos, false, event.payload().to_string())
+            .await
+        {
+            Ok(_) => {
+                debug!("Published grid outage event");
+            }
+            Err(e) => {
+                let mut state_guard = self.state.lock().await;
+                state_guard.last_error = Some(format!("Grid event publish error: {}", e));
 
-        self.create_sensor_config(
+                error!(error = %e, "Failed to publish grid outage event");
+                drop(state_guard);
+            }
+        }
+    }
+
+    pub async fn publish_data_gap(&self, gap: &DataGap) {
+        let topic = self.config.get_state_topic(&self.device_id, "events");
+        let (qos, _) = self.config.policy(TopicType::State);
+
+        match self
+            .publish(&topic, qos, false, gap.payload().to_string())
+            .await
+        {
+            Ok(_) => {
+                debug!(
+                    missed_cycles = gap.missed_cycles,
+                    "Published data gap event"
+                );
+            }
+            Err(e) => {
+                let mut state_guard = self.state.lock().await;
+                state_guard.last_error = Some(format!("Data gap publish error: {}", e));
+
+                error!(error = %e, "Failed to publish data gap event");
+                drop(state_guard);
+            }
+        }
+    }
+
+    pub async fn publish_tariff(&self, payload: &serde_json::Value) {
+        let topic = self.config.get_state_topic(&self.device_id, "tariff");
+        let (qos, retain) = self.config.policy(TopicType::State);
+
+        match self.publish(&topic, qos, retain, payload.to_string()).await {
+            Ok(_) => {
+                debug!("Published tariff energy");
+            }
+            Err(e) => {
+                let mut state_guard = self.state.lock().await;
+                state_guard.last_error = Some(format!("Tariff publish error: {}", e));
+
+                error!(error = %e, "Failed to publish tariff energy");
+                drop(state_guard);
+            }
+        }
+    }
+
+    pub async fn publish_efficiency(&self, payload: &serde_json::Value) {
+        let topic = self.config.get_state_topic(&self.device_id, "efficiency");
+        let (qos, retain) = self.config.policy(TopicType::State);
+
+        match self.publish(&topic, qos, retain, payload.to_string()).await {
+            Ok(_) => {
+                debug!("Published system efficiency");
+            }
+            Err(e) => {
+                let mut state_guard = self.state.lock().await;
+                state_guard.last_error = Some(format!("Efficiency publish error: {}", e));
+
+                error!(error = %e, "Failed to publish system efficiency");
+                drop(state_guard);
+            }
+        }
+    }
+
+    pub async fn publish_collection_latency(&self, payload: &serde_json::Value) {
+        let topic = self.config.get_state_topic(&self.device_id, "latency");
+        let (qos, retain) = self.config.policy(TopicType::State);
+
+        match self.publish(&topic, qos, retain, payload.to_string()).await {
+            Ok(_) => {
+                debug!("Published collection latency");
+            }
+            Err(e) => {
+                let mut state_guard = self.state.lock().await;
+                state_guard.last_error = Some(format!("Latency publish error: {}", e));
+
+                error!(error = %e, "Failed to publish collection latency");
+                drop(state_guard);
+            }
+        }
+    }
+
+    pub async fn publish_diagnostics(&self, snapshot: &DiagnosticsSnapshot) {
+        let topic = self.config.get_state_topic(&self.device_id, "diagnostics");
+        let (qos, retain) = self.config.policy(TopicType::State);
+
+        match self
+            .publish(&topic, qos, retain, snapshot.payload().to_string())
+            .await
+        {
+            Ok(_) => {
+                debug!("Published diagnostics");
+            }
+            Err(e) => {
+                let mut state_guard = self.state.lock().await;
+                state_guard.last_error = Some(format!("Diagnostics publish error: {}", e));
+
+                error!(error = %e, "Failed to publish diagnostics");
+                drop(state_guard);
+            }
+        }
+    }
+
+    /// Always retained so Home Assistant shows the state right after a restart.
+    pub async fn publish_health_state(&self, state: &str) {
+        let topic = self.config.get_state_topic(&self.device_id, "health");
+        let (qos, _) = self.config.policy(TopicType::State);
+
+        match self.publish(&topic, qos, true, state.to_string()).await {
+            Ok(_) => {
+                debug!("Published health state: {}", state);
+            }
+            Err(e) => {
+                let mut state_guard = self.state.lock().await;
+                state_guard.last_error = Some(format!("Health state publish error: {}", e));
+
+                error!(error = %e, "Failed to publish health state");
+                drop(state_guard);
+            }
+        }
+    }
+
+    pub async fn setup_discovery(&self) -> Result<()> {
+        info!("Setting up Home Assistant MQTT Discovery");
+
+        info!("Solar Energy Monitor starting - sending discovery messages");
+
+        self.publish_components(&self.discovery_components())
+            .await?;
+
+        info!("Home Assistant Discovery setup completed");
+        Ok(())
+    }
+
+    /// The entities every installation gets, optional ones have their own
+    /// builders below.
+    pub fn discovery_components(&self) -> Vec<DiscoveryComponent> {
+        let mut components = Vec::new();
+
+        components.push(self.sensor_component(
+            "pv_production",
+            "PV Production",
+            "power",
+            "W",
+            "measurement",
+            "{{ value_json.pv_production }}",
+        ));
+
+        components.push(self.sensor_component(
+            "consumption",
+            "Power Consumption",
+            "power",
+            "W",
+            "measurement",
+            "{{ value_json.consumption }}",
+        ));
+
+        components.push(self.sensor_component(
+            "supply_power",
+            "Grid Power",
+            "power",
+            "W",
+            "measurement",
+            "{{ value_json.supply_power }}",
+        ));
+
+        for phase in ["l1", "l2", "l3"] {
+            components.push(self.sensor_component(
+                &format!("grid_power_{phase}"),
+                &format!("Grid Power {}", phase.to_uppercase()),
+                "power",
+                "W",
+                "measurement",
+                &format!("{{{{ value_json.grid_power_{phase} }}}}"),
+            ));
+        }
+
+        components.push(self.sensor_component(
             "battery_power",
             "Battery Power",
             "power",
             "W",
             "measurement",
             "{{ value_json.battery_power }}",
-        )
-        .await?;
+        ));
 
-        self.create_sensor_config(
+        components.push(self.sensor_component(
             "battery_percent",
             "Battery Charge Level",
             "battery",
             "%",
             "measurement",
             "{{ value_json.battery_percent }}",
-        )
-        .await?;
+        ));
 
-        self.create_sensor_config(
+        components.push(self.sensor_component(
             "battery_energy_wh",
             "Battery Energy Stored",
             "energy_storage",
             "Wh",
             "measurement",
             "{{ value_json.battery_energy_wh }}",
-        )
-        .await?;
+        ));
+
+        components.push(self.sensor_component(
+            "battery_temperature",
+            "Battery Temperature",
+            "temperature",
+            "°C",
+            "measurement",
+            "{{ value_json.battery_temperature }}",
+        ));
+
+        components.push(self.sensor_component(
+            "time_to_full_minutes",
+            "Battery Time to Full",
+            "duration",
+            "min",
+            "measurement",
+            "{{ value_json.time_to_full_minutes }}",
+        ));
+
+        components.push(self.sensor_component(
+            "time_to_empty_minutes",
+            "Battery Time to Empty",
+            "duration",
+            "min",
+            "measurement",
+            "{{ value_json.time_to_empty_minutes }}",
+        ));
+
+        components.push(self.percent_sensor_component(
+            "autarky_percent",
+            "Autarky",
+            "{{ value_json.autarky_percent }}",
+        ));
 
-        self.create_energy_sensor_config(
+        components.push(self.percent_sensor_component(
+            "self_consumption_percent",
+            "Self Consumption",
+            "{{ value_json.self_consumption_percent }}",
+        ));
+
+        components.push(self.energy_sensor_component(
             "grid_buy",
             "Grid Energy Consumed",
             "{{ value_json.grid_buy }}",
-        )
-        .await?;
+        ));
 
-        self.create_energy_sensor_config(
+        components.push(self.energy_sensor_component(
             "grid_sell",
             "Grid Energy Fed-in",
             "{{ value_json.grid_sell }}",
-        )
-        .await?;
+        ));
 
-        self.create_energy_sensor_config(
+        components.push(self.energy_sensor_component(
             "production_energy",
             "Energy Produced",
             "{{ value_json.production_energy }}",
-        )
-        .await?;
+        ));
 
-        self.create_energy_sensor_config(
+        components.push(self.energy_sensor_component(
             "consumption_energy",
             "Energy Consumed",
             "{{ value_json.consumption_energy }}",
-        )
-        .await?;
+        ));
 
-        self.create_energy_sensor_config(
+        components.push(self.energy_sensor_component(
             "battery_loaded",
             "Battery Energy Loaded",
             "{{ value_json.battery_loaded }}",
-        )
-        .await?;
+        ));
 
-        self.create_energy_sensor_config(
+        components.push(self.energy_sensor_component(
             "battery_discharge",
             "Battery Energy Discharged",
             "{{ value_json.battery_discharge }}",
-        )
-        .await?;
+        ));
+
+        components.push(self.energy_sensor_component(
+            "self_consumed_energy",
+            "Solar Energy Self-Consumed",
+            "{{ value_json.self_consumed_energy }}",
+        ));
 
-        self.create_number_sensor_config(
+        components.push(self.number_sensor_component(
             "battery_cycles",
             "Battery Cycles",
             "{{ value_json.battery_cycles }}",
-        )
-        .await?;
+        ));
 
-        self.create_text_sensor_config(
+        components.push(self.text_sensor_component(
             "battery_state",
             "Battery Status",
             "{{ value_json.battery_state }}",
-        )
-        .await?;
+        ));
 
-        self.create_text_sensor_config(
+        components.push(self.text_sensor_component(
             "supply_state",
             "Grid Status",
             "{{ value_json.supply_state }}",
-        )
-        .await?;
+        ));
+
+        components.push(self.button_component(
+            "refresh",
+            "Refresh Now",
+            &self.refresh_topic(),
+            REFRESH_PAYLOAD,
+        ));
+
+        components
+    }
+
+    /// Publishes a discovery config, retained unless the discovery policy
+    /// says otherwise. With `discovery_delay_ms` set,
+    /// every `discovery_batch_size` messages are followed by a pause so a small
+    /// broker is not flooded. With `await_discovery_ack` the PUBACK has to
+    /// arrive before the next config is sent.
+    async fn publish_discovery(&self, topic: &str, config: &serde_json::Value) -> Result<()> {
+        let published = self.discovery_published.fetch_add(1, Ordering::SeqCst);
+        let batch_size = self.config.discovery_batch_size.max(1);
+        if self.config.discovery_delay_ms > 0
+            && published > 0
+            && published.is_multiple_of(batch_size)
+        {
+            tokio::time::sleep(Duration::from_millis(self.config.discovery_delay_ms)).await;
+        }
+
+        let mut acks = self.pub_acks.subscribe();
+        let acks_before = *acks.borrow_and_update();
+
+        let (qos, retain) = self.config.policy(TopicType::Discovery);
+        self.publish(topic, qos, retain, config.to_string()).await?;
+
+        if self.config.await_discovery_ack
+            && qos != QoS::AtMostOnce
+            && !self.dry_run.load(Ordering::SeqCst)
+        {
+            tokio::time::timeout(
+                Duration::from_millis(self.config.discovery_ack_timeout_ms),
+                acks.wait_for(|count| *count > acks_before),
+            )
+            .await
+            .map_err(|_| eyre!("No PUBACK for discovery config on {}", topic))??;
+        }
 
-        info!("Home Assistant Discovery setup completed");
         Ok(())
     }
 
-    async fn create_sensor_config(
+    /// `publish_discovery` with up to `DISCOVERY_ATTEMPTS` tries, backing
+    /// off between them.
+    async fn publish_discovery_with_retry(
+        &self,
+        object_id: &str,
+        topic: &str,
+        config: &serde_json::Value,
+    ) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            match self.publish_discovery(topic, config).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < DISCOVERY_ATTEMPTS => {
+                    warn!(
+                        component = object_id,
+                        attempt,
+                        error = %e,
+                        "Discovery publish failed, retrying"
+                    );
+                    tokio::time::sleep(DISCOVERY_RETRY_DELAY * 2_u32.pow(attempt - 1)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub async fn setup_battery_limit_discovery(&self) -> Result<()> {
+        self.publish_components(&self.battery_limit_components())
+            .await
+    }
+
+    pub fn battery_limit_components(&self) -> Vec<DiscoveryComponent> {
+        vec![
+            self.binary_sensor_component(
+                "battery_power_limited",
+                "Battery Power Limited",
+                "problem",
+                "{{ 'ON' if value_json.battery_power_limited else 'OFF' }}",
+            ),
+            self.sensor_component(
+                "battery_charge_limit",
+                "Battery Charge Limit",
+                "power",
+                "W",
+                "measurement",
+                "{{ value_json.battery_charge_limit }}",
+            ),
+            self.sensor_component(
+                "battery_discharge_limit",
+                "Battery Discharge Limit",
+                "power",
+                "W",
+                "measurement",
+                "{{ value_json.battery_discharge_limit }}",
+            ),
+        ]
+    }
+
+    /// AC and DC production side by side, with `production_source = "both"`.
+    pub fn production_source_components(&self) -> Vec<DiscoveryComponent> {
+        vec![
+            self.sensor_component(
+                "pv_production_ac",
+                "PV Production AC",
+                "power",
+                "W",
+                "measurement",
+                "{{ value_json.pv_production_ac }}",
+            ),
+            self.sensor_component(
+                "pv_production_dc",
+                "PV Production DC",
+                "power",
+                "W",
+                "measurement",
+                "{{ value_json.pv_production_dc }}",
+            ),
+        ]
+    }
+
+    pub fn sync_button_component(&self) -> DiscoveryComponent {
+        self.button_component(
+            "sync_cache",
+            "Sync Cache Now",
+            &self.refresh_topic(),
+            SYNC_PAYLOAD,
+        )
+    }
+
+    /// Sent to the refresh topic with [`SYNC_PAYLOAD`].
+    pub fn is_sync_request(&self, message: &IncomingMessage) -> bool {
+        message.topic == self.refresh_topic() && message.payload.trim() == SYNC_PAYLOAD
+    }
+
+    /// Consumption from the external meter and its difference to the
+    /// inverter.
+    pub fn external_meter_components(&self) -> Vec<DiscoveryComponent> {
+        vec![
+            self.sensor_component(
+                "consumption_external",
+                "External Meter Consumption",
+                "power",
+                "W",
+                "measurement",
+                "{{ value_json.consumption_external }}",
+            ),
+            self.sensor_component(
+                "consumption_discrepancy",
+                "Consumption Discrepancy",
+                "power",
+                "W",
+                "measurement",
+                "{{ value_json.consumption_discrepancy }}",
+            ),
+        ]
+    }
+
+    pub async fn setup_grid_charge_discovery(&self) -> Result<()> {
+        self.publish_components(&[self.grid_charge_component()])
+            .await
+    }
+
+    pub fn grid_charge_component(&self) -> DiscoveryComponent {
+        self.binary_sensor_component(
+            "battery_charging_from_grid",
+            "Battery Charging From Grid",
+            "battery_charging",
+            "{{ 'ON' if value_json.battery_charging_from_grid else 'OFF' }}",
+        )
+    }
+
+    /// Discovery config of one entity: `fields` plus the name, unique id and
+    /// the `device`, `origin` and `availability` blocks every entity shares.
+    fn entity_config(
+        &self,
+        object_id: &str,
+        name: &str,
+        fields: serde_json::Value,
+    ) -> serde_json::Value {
+        let mut config = json!({
+            "name": name,
+            "unique_id": format!("{}_{}", self.device_id, object_id),
+            "device": self.device_info(),
+            "origin": self.origin_info(),
+            "availability": self.availability_info(),
+        });
+        if let (Some(config), serde_json::Value::Object(fields)) = (config.as_object_mut(), fields)
+        {
+            config.extend(fields);
+        }
+        config
+    }
+
+    fn availability_info(&self) -> serde_json::Value {
+        json!({
+            "topic": self.config.get_availability_topic(&self.device_id),
+            "payload_available": "online",
+            "payload_not_available": "offline"
+        })
+    }
+
+    /// `device` block shared by every discovery config, so all entities end
+    /// up on the same Home Assistant device.
+    fn device_info(&self) -> serde_json::Value {
+        let mut device = json!({
+            "identifiers": [&self.device_id],
+            "name": &self.config.device_name,
+            "model": &self.config.device_model,
+            "manufacturer": &self.config.device_manufacturer,
+            "serial_number": &self.device_id,
+            "hw_version": "1.0",
+            "sw_version": env!("CARGO_PKG_VERSION")
+        });
+        if let Some(area) = &self.config.device_suggested_area {
+            device["suggested_area"] = json!(area);
+        }
+        if let Some(url) = &self.config.device_configuration_url {
+            device["configuration_url"] = json!(url);
+        }
+        device
+    }
+
+    fn origin_info(&self) -> serde_json::Value {
+        json!({
+            "name": "PV API Solar Monitor",
+            "sw": env!("CARGO_PKG_VERSION"),
+            "url": "https://github.com/your-repo/pv_api"
+        })
+    }
+
+    fn sensor_component(
         &self,
         sensor_id: &str,
         name: &str,
@@ -389,238 +1475,581 @@ impl SolarMqttClient {
         unit: &str,
         state_class: &str,
         value_template: &str,
-    ) -> Result<()> {
-        let discovery_topic = self
-            .config
-            .get_discovery_topic("sensor", &self.device_id, sensor_id);
+    ) -> DiscoveryComponent {
         let state_topic = self.config.get_state_topic(&self.device_id, "power");
-        let availability_topic = self.config.get_availability_topic(&self.device_id);
-
-        let config = json!({
-            "name": name,
-            "unique_id": format!("{}_{}", self.device_id, sensor_id),
-            "state_topic": state_topic,
-            "value_template": value_template,
-            "device_class": device_class,
-            "unit_of_measurement": unit,
-            "state_class": state_class,
-            "device": {
-                "identifiers": [&self.device_id],
-                "name": "Solar Energy Monitor",
-                "model": "PV API v0.1.0",
-                "manufacturer": "Custom",
-                "serial_number": &self.device_id,
-                "hw_version": "1.0",
-                "sw_version": env!("CARGO_PKG_VERSION")
-            },
-            "origin": {
-                "name": "PV API Solar Monitor",
-                "sw": env!("CARGO_PKG_VERSION"),
-                "url": "https://github.com/your-repo/pv_api"
-            },
-            "availability": {
-                "topic": availability_topic,
-                "payload_available": "online",
-                "payload_not_available": "offline"
-            }
-        });
 
-        self.client
-            .publish(
-                &discovery_topic,
-                self.config.to_qos(),
-                true, // retain
-                config.to_string(),
-            )
-            .await?;
+        let config = self.entity_config(
+            sensor_id,
+            name,
+            json!({
+                "state_topic": state_topic,
+                "value_template": value_template,
+                "device_class": device_class,
+                "unit_of_measurement": unit,
+                "state_class": state_class,
+            }),
+        );
 
-        debug!("Created sensor config for {}", sensor_id);
-        Ok(())
+        DiscoveryComponent {
+            platform: "sensor",
+            object_id: sensor_id.to_string(),
+            config,
+        }
     }
 
-    async fn create_energy_sensor_config(
+    fn energy_sensor_component(
         &self,
         sensor_id: &str,
         name: &str,
         value_template: &str,
-    ) -> Result<()> {
-        let discovery_topic = self
-            .config
-            .get_discovery_topic("sensor", &self.device_id, sensor_id);
+    ) -> DiscoveryComponent {
         let state_topic = self.config.get_state_topic(&self.device_id, "energy");
-        let availability_topic = self.config.get_availability_topic(&self.device_id);
 
-        let config = json!({
-            "name": name,
-            "unique_id": format!("{}_{}", self.device_id, sensor_id),
-            "state_topic": state_topic,
-            "value_template": value_template,
-            "device_class": "energy",
-            "unit_of_measurement": "kWh",
-            "state_class": "total_increasing",
-            "device": {
-                "identifiers": [&self.device_id],
-                "name": "Solar Energy Monitor",
-                "model": "PV API v0.1.0",
-                "manufacturer": "Custom",
-                "serial_number": &self.device_id,
-                "hw_version": "1.0",
-                "sw_version": env!("CARGO_PKG_VERSION")
-            },
-            "origin": {
-                "name": "PV API Solar Monitor",
-                "sw": env!("CARGO_PKG_VERSION"),
-                "url": "https://github.com/your-repo/pv_api"
-            },
-            "availability": {
-                "topic": availability_topic,
-                "payload_available": "online",
-                "payload_not_available": "offline"
-            }
-        });
+        let config = self.entity_config(
+            sensor_id,
+            name,
+            json!({
+                "state_topic": state_topic,
+                "value_template": value_template,
+                "device_class": "energy",
+                "unit_of_measurement": self.config.energy_unit.symbol(),
+                "state_class": "total_increasing",
+            }),
+        );
 
-        self.client
-            .publish(
-                &discovery_topic,
-                self.config.to_qos(),
-                true,
-                config.to_string(),
-            )
-            .await?;
+        DiscoveryComponent {
+            platform: "sensor",
+            object_id: sensor_id.to_string(),
+            config,
+        }
+    }
 
-        debug!("Created energy sensor config for {}", sensor_id);
-        Ok(())
+    // Ratios have no matching Home Assistant device class
+    fn percent_sensor_component(
+        &self,
+        sensor_id: &str,
+        name: &str,
+        value_template: &str,
+    ) -> DiscoveryComponent {
+        let state_topic = self.config.get_state_topic(&self.device_id, "power");
+
+        let config = self.entity_config(
+            sensor_id,
+            name,
+            json!({
+                "state_topic": state_topic,
+                "value_template": value_template,
+                "unit_of_measurement": "%",
+                "state_class": "measurement",
+            }),
+        );
+
+        DiscoveryComponent {
+            platform: "sensor",
+            object_id: sensor_id.to_string(),
+            config,
+        }
     }
 
-    async fn create_text_sensor_config(
+    fn binary_sensor_component(
         &self,
         sensor_id: &str,
         name: &str,
+        device_class: &str,
         value_template: &str,
-    ) -> Result<()> {
-        let discovery_topic = self
-            .config
-            .get_discovery_topic("sensor", &self.device_id, sensor_id);
+    ) -> DiscoveryComponent {
+        let state_topic = self.config.get_state_topic(&self.device_id, "power");
+
+        let config = self.entity_config(
+            sensor_id,
+            name,
+            json!({
+                "state_topic": state_topic,
+                "value_template": value_template,
+                "device_class": device_class,
+                "payload_on": "ON",
+                "payload_off": "OFF",
+            }),
+        );
+
+        DiscoveryComponent {
+            platform: "binary_sensor",
+            object_id: sensor_id.to_string(),
+            config,
+        }
+    }
+
+    fn text_sensor_component(
+        &self,
+        sensor_id: &str,
+        name: &str,
+        value_template: &str,
+    ) -> DiscoveryComponent {
         let state_topic = self.config.get_state_topic(&self.device_id, "state");
-        let availability_topic = self.config.get_availability_topic(&self.device_id);
 
-        let config = json!({
-            "name": name,
-            "unique_id": format!("{}_{}", self.device_id, sensor_id),
-            "state_topic": state_topic,
-            "value_template": value_template,
-            "device": {
-                "identifiers": [&self.device_id],
-                "name": "Solar Energy Monitor",
-                "model": "PV API v0.1.0",
-                "manufacturer": "Custom",
-                "serial_number": &self.device_id,
-                "hw_version": "1.0",
-                "sw_version": env!("CARGO_PKG_VERSION")
-            },
-            "origin": {
-                "name": "PV API Solar Monitor",
-                "sw": env!("CARGO_PKG_VERSION"),
-                "url": "https://github.com/your-repo/pv_api"
-            },
-            "availability": {
-                "topic": availability_topic,
-                "payload_available": "online",
-                "payload_not_available": "offline"
-            }
-        });
+        let config = self.entity_config(
+            sensor_id,
+            name,
+            json!({
+                "state_topic": state_topic,
+                "value_template": value_template,
+            }),
+        );
 
-        self.client
-            .publish(
-                &discovery_topic,
-                self.config.to_qos(),
-                true,
-                config.to_string(),
-            )
-            .await?;
+        DiscoveryComponent {
+            platform: "sensor",
+            object_id: sensor_id.to_string(),
+            config,
+        }
+    }
+
+    fn number_sensor_component(
+        &self,
+        sensor_id: &str,
+        name: &str,
+        value_template: &str,
+    ) -> DiscoveryComponent {
+        let state_topic = self.config.get_state_topic(&self.device_id, "energy");
+
+        let config = self.entity_config(
+            sensor_id,
+            name,
+            json!({
+                "state_topic": state_topic,
+                "value_template": value_template,
+                "state_class": "total",
+            }),
+        );
+
+        DiscoveryComponent {
+            platform: "sensor",
+            object_id: sensor_id.to_string(),
+            config,
+        }
+    }
+
+    fn button_component(
+        &self,
+        button_id: &str,
+        name: &str,
+        command_topic: &str,
+        payload_press: &str,
+    ) -> DiscoveryComponent {
+        let config = self.entity_config(
+            button_id,
+            name,
+            json!({
+                "command_topic": command_topic,
+                "payload_press": payload_press,
+            }),
+        );
+
+        DiscoveryComponent {
+            platform: "button",
+            object_id: button_id.to_string(),
+            config,
+        }
+    }
+
+    pub async fn publish_device_attributes(&self, attributes: &serde_json::Value) -> Result<()> {
+        let topic = self.config.get_state_topic(&self.device_id, "attributes");
+        let (qos, _) = self.config.policy(TopicType::State);
 
-        debug!("Created text sensor config for {}", sensor_id);
+        self.publish(
+            &topic,
+            qos,
+            true, // retain
+            attributes.to_string(),
+        )
+        .await?;
+
+        debug!("Published device attributes");
         Ok(())
     }
 
-    async fn create_number_sensor_config(
+    pub async fn create_device_info_config(&self) -> Result<()> {
+        self.publish_components(&[self.device_info_component()])
+            .await
+    }
+
+    pub fn device_info_component(&self) -> DiscoveryComponent {
+        let attributes_topic = self.config.get_state_topic(&self.device_id, "attributes");
+
+        let config = self.entity_config(
+            "device_info",
+            "Device Info",
+            json!({
+                "state_topic": attributes_topic,
+                "value_template": "{{ value_json.version }}",
+                "json_attributes_topic": attributes_topic,
+                "entity_category": "diagnostic",
+                "icon": "mdi:information-outline",
+            }),
+        );
+
+        DiscoveryComponent {
+            platform: "sensor",
+            object_id: "device_info".to_string(),
+            config,
+        }
+    }
+
+    pub async fn create_health_state_sensor_config(&self, options: &[&str]) -> Result<()> {
+        self.publish_components(&[self.health_state_component(options)])
+            .await
+    }
+
+    pub fn health_state_component(&self, options: &[&str]) -> DiscoveryComponent {
+        DiscoveryComponent {
+            platform: "sensor",
+            object_id: "health_state".to_string(),
+            config: self.health_state_sensor_json(options),
+        }
+    }
+
+    pub fn health_state_sensor_json(&self, options: &[&str]) -> serde_json::Value {
+        let state_topic = self.config.get_state_topic(&self.device_id, "health");
+
+        self.entity_config(
+            "health_state",
+            "Coordinator State",
+            json!({
+                "state_topic": state_topic,
+                "value_template": "{{ value }}",
+                "device_class": "enum",
+                "options": options,
+                "entity_category": "diagnostic",
+            }),
+        )
+    }
+
+    pub async fn create_diagnostic_sensor_config(&self) -> Result<()> {
+        self.publish_components(&self.diagnostic_components()).await
+    }
+
+    /// Sensors on the diagnostics topic, grouped under Diagnostic in the UI.
+    pub fn diagnostic_components(&self) -> Vec<DiscoveryComponent> {
+        vec![
+            self.diagnostic_sensor_component(
+                "diagnostics_state",
+                "Diagnostics State",
+                "{{ value_json.state }}",
+                false,
+            ),
+            self.diagnostic_sensor_component(
+                "postgres_health",
+                "PostgreSQL Health",
+                "{{ value_json.postgres_health }}",
+                false,
+            ),
+            self.diagnostic_sensor_component(
+                "mqtt_health",
+                "MQTT Health",
+                "{{ value_json.mqtt_health }}",
+                false,
+            ),
+            self.diagnostic_sensor_component(
+                "db_consecutive_failures",
+                "Consecutive DB Failures",
+                "{{ value_json.db_consecutive_failures }}",
+                true,
+            ),
+            self.diagnostic_sensor_component(
+                "cache_records",
+                "Cached Records",
+                "{{ value_json.cache_records }}",
+                true,
+            ),
+            self.diagnostic_sensor_component(
+                "data_stale",
+                "Data Stale",
+                "{{ value_json.data_stale }}",
+                false,
+            ),
+        ]
+    }
+
+    fn diagnostic_sensor_component(
         &self,
         sensor_id: &str,
         name: &str,
         value_template: &str,
-    ) -> Result<()> {
-        let discovery_topic = self
-            .config
-            .get_discovery_topic("sensor", &self.device_id, sensor_id);
-        let state_topic = self.config.get_state_topic(&self.device_id, "energy");
-        let availability_topic = self.config.get_availability_topic(&self.device_id);
+        numeric: bool,
+    ) -> DiscoveryComponent {
+        let state_topic = self.config.get_state_topic(&self.device_id, "diagnostics");
+
+        let mut config = self.entity_config(
+            sensor_id,
+            name,
+            json!({
+                "state_topic": state_topic,
+                "value_template": value_template,
+                "entity_category": "diagnostic",
+            }),
+        );
+        if numeric {
+            config["state_class"] = json!("measurement");
+        }
 
-        let config = json!({
-            "name": name,
-            "unique_id": format!("{}_{}", self.device_id, sensor_id),
-            "state_topic": state_topic,
-            "value_template": value_template,
-            "state_class": "total",
-            "device": {
-                "identifiers": [&self.device_id],
-                "name": "Solar Energy Monitor",
-                "model": "PV API v0.1.0",
-                "manufacturer": "Custom",
-                "serial_number": &self.device_id,
-                "hw_version": "1.0",
-                "sw_version": env!("CARGO_PKG_VERSION")
-            },
-            "origin": {
-                "name": "PV API Solar Monitor",
-                "sw": env!("CARGO_PKG_VERSION"),
-                "url": "https://github.com/your-repo/pv_api"
-            },
-            "availability": {
-                "topic": availability_topic,
-                "payload_available": "online",
-                "payload_not_available": "offline"
+        DiscoveryComponent {
+            platform: "sensor",
+            object_id: sensor_id.to_string(),
+            config,
+        }
+    }
+
+    /// Average poll duration as state, min/p95/max as attributes.
+    pub fn collection_latency_component(&self) -> DiscoveryComponent {
+        let state_topic = self.config.get_state_topic(&self.device_id, "latency");
+
+        let config = self.entity_config(
+            "collection_latency_ms",
+            "Collection Latency",
+            json!({
+                "state_topic": state_topic,
+                "value_template": "{{ value_json.avg_ms }}",
+                "json_attributes_topic": state_topic,
+                "unit_of_measurement": "ms",
+                "device_class": "duration",
+                "state_class": "measurement",
+                "entity_category": "diagnostic",
+            }),
+        );
+
+        DiscoveryComponent {
+            platform: "sensor",
+            object_id: "collection_latency_ms".to_string(),
+            config,
+        }
+    }
+
+    pub async fn create_efficiency_sensor_config(&self) -> Result<()> {
+        self.publish_components(&[self.efficiency_component()])
+            .await
+    }
+
+    pub fn efficiency_component(&self) -> DiscoveryComponent {
+        let state_topic = self.config.get_state_topic(&self.device_id, "efficiency");
+
+        let config = self.entity_config(
+            "system_efficiency",
+            "System Efficiency",
+            json!({
+                "state_topic": state_topic,
+                "value_template": "{{ value_json.system_efficiency }}",
+                "unit_of_measurement": "%",
+                "state_class": "measurement",
+                "entity_category": "diagnostic",
+            }),
+        );
+
+        DiscoveryComponent {
+            platform: "sensor",
+            object_id: "system_efficiency".to_string(),
+            config,
+        }
+    }
+
+    pub async fn publish_daily_totals(&self, payload: &serde_json::Value) {
+        let topic = self.config.get_state_topic(&self.device_id, "daily");
+        let (qos, retain) = self.config.policy(TopicType::State);
+
+        match self.publish(&topic, qos, retain, payload.to_string()).await {
+            Ok(_) => {
+                debug!("Published daily totals");
             }
-        });
+            Err(e) => {
+                let mut state_guard = self.state.lock().await;
+                state_guard.last_error = Some(format!("Daily totals publish error: {}", e));
 
-        self.client
-            .publish(
-                &discovery_topic,
-                self.config.to_qos(),
-                true,
-                config.to_string(),
-            )
-            .await?;
+                error!(error = %e, "Failed to publish daily totals");
+                drop(state_guard);
+            }
+        }
+    }
+
+    /// Production, consumption and grid energy since local midnight.
+    pub fn daily_totals_components(&self) -> Vec<DiscoveryComponent> {
+        let state_topic = self.config.get_state_topic(&self.device_id, "daily");
+
+        [
+            ("production_today", "Production Today"),
+            ("consumption_today", "Consumption Today"),
+            ("grid_import_today", "Grid Import Today"),
+            ("grid_export_today", "Grid Export Today"),
+        ]
+        .into_iter()
+        .map(|(sensor_id, name)| {
+            let config = self.entity_config(
+                sensor_id,
+                name,
+                json!({
+                    "state_topic": state_topic,
+                    "value_template": format!("{{{{ value_json.{}_kwh }}}}", sensor_id),
+                    "device_class": "energy",
+                    "unit_of_measurement": "kWh",
+                    // Resets at midnight, Home Assistant treats the drop as a new
+                    // cycle. last_reset only goes along as an attribute, Home
+                    // Assistant rejects it next to total_increasing.
+                    "state_class": "total_increasing",
+                    "json_attributes_topic": state_topic,
+                    "json_attributes_template": "{{ {'last_reset': value_json.last_reset} | tojson }}",
+                })
+            );
+
+            DiscoveryComponent {
+                platform: "sensor",
+                object_id: sensor_id.to_string(),
+                config,
+            }
+        })
+        .collect()
+    }
+
+    pub async fn create_tariff_sensor_configs(&self, windows: &[String]) -> Result<()> {
+        self.publish_components(&self.tariff_components(windows))
+            .await
+    }
+
+    /// Daily import and export sensors for every tariff window.
+    pub fn tariff_components(&self, windows: &[String]) -> Vec<DiscoveryComponent> {
+        let state_topic = self.config.get_state_topic(&self.device_id, "tariff");
+
+        let mut components = Vec::new();
+        for window in windows {
+            for (direction, label) in [("import", "Import"), ("export", "Export")] {
+                let sensor_id = format!("tariff_{}_{}", window, direction);
+
+                let config = self.entity_config(
+                    &sensor_id,
+                    &format!("Grid {} {}", label, window),
+                    json!({
+                        "state_topic": state_topic,
+                        "value_template": format!("{{{{ value_json.{}_{} }}}}", window, direction),
+                        "device_class": "energy",
+                        "unit_of_measurement": "kWh",
+                        // Resets at midnight, Home Assistant treats the drop as a new cycle
+                        "state_class": "total_increasing",
+                    }),
+                );
+
+                components.push(DiscoveryComponent {
+                    platform: "sensor",
+                    object_id: sensor_id,
+                    config,
+                });
+            }
+        }
+
+        components
+    }
+
+    /// Publishes every component as its own retained config, the `legacy`
+    /// discovery mode.
+    /// A config that still fails after its retries does not stop the
+    /// others, all failed ones are reported together in a [`DiscoveryError`].
+    pub async fn publish_components(&self, components: &[DiscoveryComponent]) -> Result<()> {
+        let mut failed = Vec::new();
+        for component in components {
+            let discovery_topic = self.config.get_discovery_topic(
+                component.platform,
+                &self.device_id,
+                &component.object_id,
+            );
+            match self
+                .publish_discovery_with_retry(
+                    &component.object_id,
+                    &discovery_topic,
+                    &component.config,
+                )
+                .await
+            {
+                Ok(()) => debug!(
+                    "Created {} config for {}",
+                    component.platform, component.object_id
+                ),
+                Err(e) => {
+                    error!(
+                        component = %component.object_id,
+                        error = %e,
+                        "Discovery publish failed, giving up"
+                    );
+                    failed.push(component.object_id.clone());
+                }
+            }
+        }
 
-        debug!("Created number sensor config for {}", sensor_id);
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(DiscoveryError { failed }.into())
+        }
+    }
+
+    /// Publishes all components in one retained device discovery message,
+    /// the `device` discovery mode.
+    pub async fn setup_device_discovery(&self, components: &[DiscoveryComponent]) -> Result<()> {
+        let discovery_topic = self.config.get_device_discovery_topic(&self.device_id);
+
+        self.publish_discovery_with_retry(
+            &self.device_id,
+            &discovery_topic,
+            &self.device_discovery_json(components),
+        )
+        .await
+        .map_err(|_| DiscoveryError {
+            failed: vec![self.device_id.clone()],
+        })?;
+
+        info!(
+            components = components.len(),
+            "Home Assistant device discovery published"
+        );
         Ok(())
     }
 
+    /// Device discovery payload: device, origin and availability once, every
+    /// entity under `components` keyed by its object id.
+    pub fn device_discovery_json(&self, components: &[DiscoveryComponent]) -> serde_json::Value {
+        let mut entries = serde_json::Map::new();
+        for component in components {
+            let mut config = component.config.clone();
+            if let Some(entity) = config.as_object_mut() {
+                for shared in ["device", "origin", "availability"] {
+                    entity.remove(shared);
+                }
+                entity.insert("platform".to_string(), json!(component.platform));
+            }
+            entries.insert(component.object_id.clone(), config);
+        }
+
+        json!({
+            "device": self.device_info(),
+            "origin": self.origin_info(),
+            "availability": self.availability_info(),
+            "qos": self.config.qos_level,
+            "components": entries
+        })
+    }
+
     pub async fn publish_availability(&self, available: bool) {
         let topic = self.config.get_availability_topic(&self.device_id);
         let payload = if available { "online" } else { "offline" };
+        let (qos, retain) = self.config.policy(TopicType::Availability);
 
-        if let Err(e) = self
-            .client
-            .publish(
-                &topic,
-                self.config.to_qos(),
-                true, // retain
-                payload.to_string(),
-            )
-            .await
-        {
+        if let Err(e) = self.publish(&topic, qos, retain, payload.to_string()).await {
             error!(error = %e, "Failed to publish availability status");
         } else {
             debug!("Published availability: {}", payload);
         }
     }
     pub async fn publish_birth_message(&self) {
+        let (qos, retain) = self.config.policy(TopicType::Availability);
         if let Err(e) = self
-            .client
             .publish(
                 &self.config.birth_topic,
-                self.config.to_qos(),
-                true,
+                qos,
+                retain,
                 self.config.birth_payload.clone(),
             )
             .await
@@ -634,10 +2063,94 @@ impl SolarMqttClient {
         }
     }
 
+    /// Returns every message received since the last call without waiting.
+    pub async fn drain_incoming(&self) -> Vec<IncomingMessage> {
+        let mut receiver = self.incoming.lock().await;
+        let mut messages = Vec::new();
+        while let Ok(message) = receiver.try_recv() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    /// Home Assistant announcing itself after a restart, at which point it has
+    /// forgotten every non-retained discovery config.
+    pub fn is_birth_message(&self, message: &IncomingMessage) -> bool {
+        message.topic == self.config.birth_topic
+            && message.payload.trim() == self.config.birth_payload
+    }
+
+    /// The last discovery setup did not get every config out.
+    pub fn discovery_pending(&self) -> bool {
+        self.discovery_pending.load(Ordering::SeqCst)
+    }
+
+    pub fn set_discovery_pending(&self, pending: bool) {
+        self.discovery_pending.store(pending, Ordering::SeqCst);
+    }
+
+    /// Number of discovery configs sent since the client was created.
+    pub fn discovery_published(&self) -> usize {
+        self.discovery_published.load(Ordering::SeqCst)
+    }
+
+    pub fn refresh_topic(&self) -> String {
+        self.config.get_state_topic(&self.device_id, "command")
+    }
+
+    /// Shared with the main loop, which waits on it between cycles.
+    pub fn refresh_signal(&self) -> Arc<Notify> {
+        self.refresh.clone()
+    }
+
+    pub async fn subscribe_to_refresh(&self) -> Result<()> {
+        let topic = self.refresh_topic();
+        self.client()
+            .await
+            .subscribe(&topic, self.config.to_qos())
+            .await?;
+        self.subscriptions.lock().await.push(topic.clone());
+        info!("Subscribed to refresh command topic: {}", topic);
+        Ok(())
+    }
+
+    pub fn command_topic(&self, command: &str) -> String {
+        self.config.get_command_topic(&self.device_id, command)
+    }
+
+    pub async fn subscribe_to_commands(&self) -> Result<()> {
+        let topic = self.command_topic("+");
+        self.client()
+            .await
+            .subscribe(&topic, self.config.to_qos())
+            .await?;
+        self.subscriptions.lock().await.push(topic.clone());
+        info!("Subscribed to command topic: {}", topic);
+        Ok(())
+    }
+
+    pub async fn publish_command_response(&self, response: &serde_json::Value) {
+        let topic = self.config.get_state_topic(&self.device_id, "response");
+
+        if let Err(e) = self
+            .publish(&topic, self.config.to_qos(), false, response.to_string())
+            .await
+        {
+            error!(error = %e, "Failed to publish command response");
+        } else {
+            debug!("Published command response");
+        }
+    }
+
     pub async fn subscribe_to_hass_status(&self) -> Result<()> {
-        self.client
+        self.client()
+            .await
             .subscribe(&self.config.birth_topic, self.config.to_qos())
             .await?;
+        self.subscriptions
+            .lock()
+            .await
+            .push(self.config.birth_topic.clone());
         info!(
             "Subscribed to Home Assistant status topic: {}",
             self.config.birth_topic
@@ -0,0 +1,117 @@
+use crate::config::NotifyConfig;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// A change of the coordinator state, as passed to a [`Notifier`].
+#[derive(Debug, Clone, Serialize)]
+pub struct TransitionEvent {
+    pub device_id: String,
+    pub from_state: String,
+    pub state: String,
+    pub reason: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl TransitionEvent {
+    pub fn message(&self) -> String {
+        format!(
+            "{}: {} -> {} ({})",
+            self.device_id, self.from_state, self.state, self.reason
+        )
+    }
+}
+
+/// Receives every state transition of the coordinator. Which of them are
+/// worth a push message is up to the implementation.
+#[async_trait]
+pub trait Notifier: Send + Sync + fmt::Debug {
+    async fn notify(&self, event: &TransitionEvent) -> Result<()>;
+}
+
+/// POSTs transitions into a degraded state as JSON to a webhook (Discord,
+/// Slack, ntfy, ...). The same state is reported at most once per
+/// `min_interval_secs`, a flapping database does not flood the channel.
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    config: NotifyConfig,
+    client: reqwest::Client,
+    last_sent: Mutex<HashMap<String, Instant>>,
+}
+
+impl WebhookNotifier {
+    /// None without a configured `webhook_url`.
+    pub fn new(config: &NotifyConfig) -> Result<Option<Self>> {
+        if config.webhook_url.is_none() {
+            return Ok(None);
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()?;
+        Ok(Some(Self {
+            config: config.clone(),
+            client,
+            last_sent: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    fn rate_limited(&self, state: &str) -> bool {
+        let interval = Duration::from_secs(self.config.min_interval_secs);
+        self.last_sent
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(state)
+            .is_some_and(|sent| sent.elapsed() < interval)
+    }
+
+    /// Only delivered notifications count, a failed one is retried with the
+    /// next transition.
+    fn record_sent(&self, state: &str) {
+        self.last_sent
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(state.to_string(), Instant::now());
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, event: &TransitionEvent) -> Result<()> {
+        if event.state == "Healthy" && !self.config.notify_recovery {
+            return Ok(());
+        }
+        if self.rate_limited(&event.state) {
+            debug!(state = %event.state, "Notification rate limited");
+            return Ok(());
+        }
+
+        let url = self.config.webhook_url.as_deref().unwrap_or_default();
+        // Discord reads `content`, Slack and Mattermost `text`
+        let body = json!({
+            "device_id": event.device_id,
+            "from_state": event.from_state,
+            "state": event.state,
+            "reason": event.reason,
+            "timestamp": event.timestamp.to_rfc3339(),
+            "content": event.message(),
+            "text": event.message(),
+        });
+        self.client
+            .post(url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        self.record_sent(&event.state);
+        debug!(state = %event.state, "Transition notification sent");
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config::GridOutageConfig;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GridEvent {
+    OutageStarted {
+        started_at: DateTime<Utc>,
+    },
+    OutageEnded {
+        started_at: DateTime<Utc>,
+        ended_at: DateTime<Utc>,
+        duration_secs: i64,
+    },
+}
+
+impl GridEvent {
+    pub fn payload(&self) -> Value {
+        let mut payload = json!(self);
+        payload["timestamp"] = json!(Utc::now().to_rfc3339());
+        payload
+    }
+}
+
+/// Reports the edges grid present -> absent and back. The grid has to stay
+/// away for `min_duration_secs` before an outage is reported, shorter
+/// dropouts produce no events at all. The outage is dated from the first
+/// offline reading, not from the moment it was confirmed.
+#[derive(Debug, Clone)]
+pub struct OutageDetector {
+    config: GridOutageConfig,
+    offline_since: Option<DateTime<Utc>>,
+    reported: bool,
+}
+
+impl OutageDetector {
+    pub fn new(config: GridOutageConfig) -> Self {
+        Self {
+            config,
+            offline_since: None,
+            reported: false,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn update(&mut self, grid_connected: bool, now: DateTime<Utc>) -> Option<GridEvent> {
+        if grid_connected {
+            let started_at = self.offline_since.take()?;
+            if !std::mem::take(&mut self.reported) {
+                return None;
+            }
+            return Some(GridEvent::OutageEnded {
+                started_at,
+                ended_at: now,
+                duration_secs: (now - started_at).num_seconds(),
+            });
+        }
+
+        let started_at = *self.offline_since.get_or_insert(now);
+        let offline_secs = (now - started_at).num_seconds();
+        if self.reported || offline_secs < self.config.min_duration_secs as i64 {
+            return None;
+        }
+
+        self.reported = true;
+        Some(GridEvent::OutageStarted { started_at })
+    }
+}
+
+#[test]
+fn test_grid_outage_events() {
+    let mut detector = OutageDetector::new(GridOutageConfig {
+        enabled: true,
+        min_duration_secs: 120,
+    });
+    let start = Utc::now();
+    let at = |secs: i64| start + chrono::Duration::seconds(secs);
+
+    assert_eq!(detector.update(true, at(0)), None);
+
+    // Brief dropout below the minimum duration
+    assert_eq!(detector.update(false, at(60)), None);
+    assert_eq!(detector.update(false, at(120)), None);
+    assert_eq!(detector.update(true, at(180)), None);
+
+    // Real outage, confirmed once it lasted two minutes
+    assert_eq!(detector.update(false, at(240)), None);
+    assert_eq!(detector.update(false, at(300)), None);
+    assert_eq!(
+        detector.update(false, at(360)),
+        Some(GridEvent::OutageStarted {
+            started_at: at(240)
+        })
+    );
+    assert_eq!(detector.update(false, at(420)), None);
+    assert_eq!(
+        detector.update(true, at(540)),
+        Some(GridEvent::OutageEnded {
+            started_at: at(240),
+            ended_at: at(540),
+            duration_secs: 300,
+        })
+    );
+    assert_eq!(detector.update(true, at(600)), None);
+
+    let payload = GridEvent::OutageEnded {
+        started_at: at(240),
+        ended_at: at(540),
+        duration_secs: 300,
+    }
+    .payload();
+    assert_eq!(payload["event"], "outage_ended");
+    assert_eq!(payload["duration_secs"], 300);
+}
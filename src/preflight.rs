@@ -0,0 +1,109 @@
+use crate::collector::send_request;
+use crate::config::{Config, StorageBackend};
+use crate::db::PostgresDatabase;
+use crate::mqtt::SolarMqttClient;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use std::fmt;
+use std::time::Duration;
+
+/// Upper bound for every single connectivity check.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    /// None when the check does not apply to this config
+    pub outcome: Option<Result<(), String>>,
+}
+
+impl CheckResult {
+    fn new(name: &'static str, result: Result<()>) -> Self {
+        Self {
+            name,
+            outcome: Some(result.map_err(|e| format!("{:#}", e))),
+        }
+    }
+
+    fn skipped(name: &'static str) -> Self {
+        Self {
+            name,
+            outcome: None,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        !matches!(self.outcome, Some(Err(_)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PreflightReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl PreflightReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(CheckResult::passed)
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let (status, detail) = match &check.outcome {
+                Some(Ok(())) => ("PASS", ""),
+                Some(Err(e)) => ("FAIL", e.as_str()),
+                None => ("SKIP", "not used by this config"),
+            };
+            writeln!(f, "{:<10} {:<4}  {}", check.name, status, detail)?;
+        }
+        write!(
+            f,
+            "{}",
+            if self.passed() {
+                "All checks passed"
+            } else {
+                "Some checks failed"
+            }
+        )
+    }
+}
+
+/// Validates `config` and tries every dependency once: one inverter
+/// request, `SELECT 1` on Postgres and an MQTT connect. Nothing is stored or
+/// published, the MQTT session is closed cleanly so the last will is not
+/// sent either.
+pub async fn run_preflight(config: &Config) -> PreflightReport {
+    let mut checks = vec![CheckResult::new("config", config.validate())];
+
+    for config in config.per_inverter() {
+        let channel = format!(
+            "{}/{}",
+            config.pv_baseaddress, config.channel_map.production_power
+        );
+        let request = async {
+            tokio::time::timeout(CHECK_TIMEOUT, send_request(&channel, &config.pv_auth))
+                .await
+                .map_err(|_| eyre!("No answer within {:?}", CHECK_TIMEOUT))??;
+            Ok(())
+        };
+        checks.push(CheckResult::new("inverter", request.await));
+
+        if config.storage_backend == StorageBackend::Postgres {
+            checks.push(CheckResult::new(
+                "postgres",
+                PostgresDatabase::ping(&config.database_config, CHECK_TIMEOUT).await,
+            ));
+        } else {
+            checks.push(CheckResult::skipped("postgres"));
+        }
+    }
+
+    checks.push(CheckResult::new(
+        "mqtt",
+        SolarMqttClient::check_connection(&config.mqtt_config, CHECK_TIMEOUT).await,
+    ));
+
+    PreflightReport { checks }
+}
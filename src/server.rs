@@ -0,0 +1,345 @@
+use crate::calculator::{MqttPayload, ProcessedData};
+use crate::db::StateTransitionRecord;
+use crate::latency::LatencyStats;
+use crate::metrics::Metrics;
+use crate::snapshot::Snapshot;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Result, WrapErr};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{Mutex, broadcast};
+use tracing::{debug, info};
+
+/// Readings kept in `CoordinatorStatus::recent`.
+pub const RECENT_READINGS: usize = 120;
+
+/// State transitions kept in `CoordinatorStatus::transitions`.
+pub const RECENT_TRANSITIONS: i64 = 20;
+
+/// Frames buffered per WebSocket client before a slow one skips ahead.
+pub const LIVE_FEED_CAPACITY: usize = 16;
+
+/// Snapshot of the coordinator written by the main loop after every cycle
+/// and read by the HTTP server and the terminal dashboard.
+#[derive(Debug, Clone, Serialize)]
+pub struct CoordinatorStatus {
+    pub state: String,
+    pub postgres: String,
+    pub mqtt: String,
+    pub last_successful_cycle: Option<DateTime<Utc>>,
+    pub postgres_consecutive_failures: u32,
+    pub mqtt_failed_publish_count: u32,
+    /// Power and energy rows waiting in the SQLite cache
+    pub cache_backlog: u64,
+    /// Inverter poll durations over the last polls
+    pub collection_latency: Option<LatencyStats>,
+    /// Inverter feed looks frozen
+    pub data_stale: bool,
+    /// Last state transitions from the SQLite cache, newest first
+    pub transitions: Vec<StateTransitionRecord>,
+    /// Ring buffer of the last readings, newest last
+    #[serde(skip)]
+    pub recent: VecDeque<Snapshot>,
+}
+
+impl Default for CoordinatorStatus {
+    fn default() -> Self {
+        Self {
+            state: "Starting".to_string(),
+            postgres: "Unknown".to_string(),
+            mqtt: "Unknown".to_string(),
+            last_successful_cycle: None,
+            postgres_consecutive_failures: 0,
+            mqtt_failed_publish_count: 0,
+            cache_backlog: 0,
+            collection_latency: None,
+            data_stale: false,
+            transitions: Vec::new(),
+            recent: VecDeque::new(),
+        }
+    }
+}
+
+impl CoordinatorStatus {
+    /// Appends a reading unless it is already the newest one.
+    pub fn push_reading(&mut self, snapshot: &Snapshot) {
+        if self
+            .recent
+            .back()
+            .is_some_and(|last| last.collected_at >= snapshot.collected_at)
+        {
+            return;
+        }
+
+        if self.recent.len() == RECENT_READINGS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(snapshot.clone());
+    }
+}
+
+pub type SharedStatus = Arc<Mutex<CoordinatorStatus>>;
+
+/// State JSON of every successful collection, fanned out to the `/ws`
+/// clients. Publishing never waits on a client, one that falls behind
+/// loses its oldest frames instead.
+#[derive(Debug, Clone)]
+pub struct LiveFeed {
+    sender: broadcast::Sender<String>,
+}
+
+impl Default for LiveFeed {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(LIVE_FEED_CAPACITY).0,
+        }
+    }
+}
+
+impl LiveFeed {
+    pub fn publish(&self, data: &ProcessedData) {
+        // No connected client is not an error
+        let _ = self.sender.send(data.to_state_json().to_string());
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.sender.subscribe()
+    }
+}
+
+/// What the HTTP server shows of one inverter.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceState {
+    pub device_id: String,
+    pub status: SharedStatus,
+    pub metrics: Metrics,
+    pub live: LiveFeed,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AppState {
+    /// One entry per inverter, `/ws` without a device id follows the first
+    pub devices: Vec<DeviceState>,
+}
+
+impl AppState {
+    fn device(&self, device_id: &str) -> Option<&DeviceState> {
+        self.devices
+            .iter()
+            .find(|device| device.device_id == device_id)
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/metrics", get(metrics))
+        .route("/ws", get(live))
+        .route("/ws/{device_id}", get(live_device))
+        .with_state(state)
+}
+
+pub async fn serve(bind_addr: &str, state: AppState) -> Result<()> {
+    let listener = TcpListener::bind(bind_addr)
+        .await
+        .wrap_err_with(|| format!("Failed to bind HTTP server to {}", bind_addr))?;
+    info!("HTTP status server listening on {}", bind_addr);
+
+    serve_on(listener, state).await
+}
+
+pub async fn serve_on(listener: TcpListener, state: AppState) -> Result<()> {
+    axum::serve(listener, router(state))
+        .await
+        .wrap_err("HTTP status server failed")
+}
+
+/// The status of a single inverter as is, with several inverters an object
+/// keyed by device id.
+async fn health(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let status_json = |status: &CoordinatorStatus| serde_json::to_value(status).unwrap_or_default();
+    if let [device] = state.devices.as_slice() {
+        return Json(status_json(&*device.status.lock().await));
+    }
+
+    let mut statuses = serde_json::Map::new();
+    for device in &state.devices {
+        statuses.insert(
+            device.device_id.clone(),
+            status_json(&*device.status.lock().await),
+        );
+    }
+    Json(serde_json::Value::Object(statuses))
+}
+
+/// Every inverter's metrics, told apart by their `device_id` label.
+async fn metrics(State(state): State<AppState>) -> String {
+    Metrics::render_all(state.devices.iter().map(|device| &device.metrics))
+}
+
+async fn live(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
+    match state.devices.first() {
+        Some(device) => live_feed(ws, device).await,
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn live_device(
+    ws: WebSocketUpgrade,
+    Path(device_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    match state.device(&device_id) {
+        Some(device) => live_feed(ws, device).await,
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Sends the newest reading right away, then one frame per cycle.
+async fn live_feed(ws: WebSocketUpgrade, device: &DeviceState) -> Response {
+    let frames = device.live.subscribe();
+    let latest = device
+        .status
+        .lock()
+        .await
+        .recent
+        .back()
+        .map(|snapshot| snapshot.power_data.to_state_json().to_string());
+
+    ws.on_upgrade(move |socket| stream_live(socket, latest, frames))
+}
+
+async fn stream_live(
+    mut socket: WebSocket,
+    latest: Option<String>,
+    mut frames: broadcast::Receiver<String>,
+) {
+    if let Some(latest) = latest
+        && socket.send(Message::Text(latest.into())).await.is_err()
+    {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Ok(frame) => {
+                    if socket.send(Message::Text(frame.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!(skipped, "WebSocket client too slow, frames dropped");
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    debug!("WebSocket client disconnected");
+}
+
+#[tokio::test]
+async fn test_health_endpoint() {
+    let status = SharedStatus::default();
+    {
+        let mut guard = status.lock().await;
+        guard.state = "Healthy".to_string();
+        guard.postgres = "Healthy".to_string();
+        guard.mqtt = "Healthy".to_string();
+        guard.last_successful_cycle = Some(Utc::now());
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve_on(
+        listener,
+        AppState {
+            devices: vec![DeviceState {
+                device_id: "pv_api".to_string(),
+                status,
+                metrics: Metrics::new(),
+                live: LiveFeed::default(),
+            }],
+        },
+    ));
+
+    let response: serde_json::Value = reqwest::get(format!("http://{}/health", addr))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    for key in [
+        "state",
+        "postgres",
+        "mqtt",
+        "last_successful_cycle",
+        "postgres_consecutive_failures",
+        "mqtt_failed_publish_count",
+        "collection_latency",
+    ] {
+        assert!(response.get(key).is_some(), "missing key {}", key);
+    }
+    assert_eq!(response["state"], "Healthy");
+}
+
+#[tokio::test]
+async fn test_health_and_metrics_for_several_devices() {
+    let devices: Vec<DeviceState> = ["roof", "garage"]
+        .into_iter()
+        .map(|device_id| DeviceState {
+            device_id: device_id.to_string(),
+            status: SharedStatus::default(),
+            metrics: Metrics::for_device(device_id),
+            live: LiveFeed::default(),
+        })
+        .collect();
+    devices[0].metrics.record_collection_failure();
+    devices[1].status.lock().await.state = "Degraded".to_string();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve_on(listener, AppState { devices }));
+
+    let health: serde_json::Value = reqwest::get(format!("http://{}/health", addr))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(health.get("roof").is_some(), "missing device roof");
+    assert_eq!(health["garage"]["state"], "Degraded");
+
+    let metrics = reqwest::get(format!("http://{}/metrics", addr))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(metrics.contains("device_id=\"roof\""), "{}", metrics);
+    assert!(metrics.contains("device_id=\"garage\""), "{}", metrics);
+
+    tokio_tungstenite::connect_async(format!("ws://{}/ws/garage", addr))
+        .await
+        .unwrap();
+    let missing = tokio_tungstenite::connect_async(format!("ws://{}/ws/unknown", addr)).await;
+    assert!(
+        matches!(missing, Err(tokio_tungstenite::tungstenite::Error::Http(ref response)) if response.status() == 404),
+        "{:?}",
+        missing
+    );
+}
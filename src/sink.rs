@@ -0,0 +1,64 @@
+use crate::calculator::{DataHistory, ProcessedData};
+use crate::db::{PostgresDatabase, PostgresHealth};
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+use std::fmt;
+use tracing::debug;
+
+/// Destination for the readings of every cycle. The coordinator only writes
+/// through this trait, so a different backend can replace Postgres without
+/// touching the state machine. Cache sync, pruning and tariff bookkeeping
+/// stay Postgres specific.
+#[async_trait]
+pub trait MetricSink: Send + Sync + fmt::Debug {
+    async fn store_power(&self, data: &ProcessedData) -> Result<()>;
+
+    async fn store_energy(&self, data: &DataHistory) -> Result<()>;
+
+    /// True when the sink accepts writes again, drives the recovery checks.
+    async fn health_check(&self) -> Result<bool>;
+}
+
+#[async_trait]
+impl MetricSink for PostgresDatabase {
+    async fn store_power(&self, data: &ProcessedData) -> Result<()> {
+        self.store_power_data(data).await
+    }
+
+    async fn store_energy(&self, data: &DataHistory) -> Result<()> {
+        self.store_energy_data(data).await
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(PostgresDatabase::health_check(self).await? == PostgresHealth::Healthy)
+    }
+}
+
+/// Accepts and drops every reading, for MQTT-only setups without a database.
+/// Always healthy, so the coordinator never leaves Healthy because of storage.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullSink;
+
+#[async_trait]
+impl MetricSink for NullSink {
+    async fn store_power(&self, data: &ProcessedData) -> Result<()> {
+        debug!(
+            production = data.full_production,
+            consumption = data.consumption,
+            "No storage backend, power data not stored"
+        );
+        Ok(())
+    }
+
+    async fn store_energy(&self, data: &DataHistory) -> Result<()> {
+        debug!(
+            production_energy = data.production_energy,
+            "No storage backend, energy data not stored"
+        );
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(true)
+    }
+}
@@ -0,0 +1,124 @@
+use crate::calculator::{
+    ProcessedData, SensorValue, SupplyState, autarky_percent, battery_state,
+    self_consumption_percent, supply_state,
+};
+
+/// Smoothed power flows in W, signed like the MQTT payload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SmoothedPower {
+    production: f64,
+    consumption: f64,
+    grid: f64,
+    battery: f64,
+}
+
+impl SmoothedPower {
+    fn from_data(data: &ProcessedData) -> Self {
+        Self {
+            production: data.full_production as f64,
+            consumption: data.consumption as f64,
+            grid: data.supply_state.power_value() as f64,
+            battery: data.battery_status.battery_state.power_value() as f64,
+        }
+    }
+}
+
+/// Exponential moving average over production, consumption, grid and battery
+/// power for the MQTT output, the database keeps the raw readings. The first
+/// reading after a start is taken as is. `alpha = 1` turns smoothing off,
+/// smaller values follow changes more slowly.
+#[derive(Debug, Clone)]
+pub struct PowerSmoother {
+    alpha: f64,
+    empty_threshold: u8,
+    full_threshold: u8,
+    state: Option<SmoothedPower>,
+}
+
+impl PowerSmoother {
+    pub fn new(alpha: f64, empty_threshold: u8, full_threshold: u8) -> Self {
+        Self {
+            alpha,
+            empty_threshold,
+            full_threshold,
+            state: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.alpha < 1.0
+    }
+
+    /// `data` with the power values replaced by their moving average. The
+    /// states and percentages are derived again from the smoothed values, a
+    /// lost grid stays offline.
+    pub fn apply(&mut self, data: &ProcessedData) -> ProcessedData {
+        if !self.is_enabled() {
+            return data.clone();
+        }
+
+        let current = SmoothedPower::from_data(data);
+        let smoothed = match self.state {
+            Some(previous) => {
+                let ema =
+                    |previous: f64, current: f64| previous + self.alpha * (current - previous);
+                SmoothedPower {
+                    production: ema(previous.production, current.production),
+                    consumption: ema(previous.consumption, current.consumption),
+                    grid: ema(previous.grid, current.grid),
+                    battery: ema(previous.battery, current.battery),
+                }
+            }
+            None => current,
+        };
+        self.state = Some(smoothed);
+
+        let mut data = data.clone();
+        data.full_production = smoothed.production.round() as u16;
+        data.consumption = smoothed.consumption.round() as u16;
+        if !matches!(data.supply_state, SupplyState::Offline) {
+            data.supply_state = supply_state(None, smoothed.grid.round() as i32);
+        }
+        data.battery_status.battery_state = battery_state(
+            smoothed.battery.round() as i32,
+            data.battery_status.battery_percent,
+            self.empty_threshold,
+            self.full_threshold,
+        );
+        data.autarky_percent = autarky_percent(data.consumption, &data.supply_state);
+        data.self_consumption_percent =
+            self_consumption_percent(data.full_production, &data.supply_state);
+        data
+    }
+}
+
+#[test]
+fn test_smoothing_step_response() {
+    let reading = |production: u16, grid_power: i32| ProcessedData {
+        full_production: production,
+        consumption: 500,
+        supply_state: supply_state(Some(true), grid_power),
+        ..ProcessedData::default()
+    };
+
+    let mut smoother = PowerSmoother::new(0.5, 10, 95);
+    let first = smoother.apply(&reading(0, 500));
+    assert_eq!(first.full_production, 0);
+
+    // Step from 0 to 2000 W: half of the remaining distance per cycle
+    let expected = [1000, 1500, 1750, 1875, 1938];
+    for expected in expected {
+        let smoothed = smoother.apply(&reading(2000, -1500));
+        assert_eq!(smoothed.full_production, expected);
+        assert_eq!(smoothed.consumption, 500);
+    }
+
+    // Grid went from 500 W demand to 1500 W surplus, 500 - 2000 * (1 - 0.5^6)
+    let smoothed = smoother.apply(&reading(2000, -1500));
+    assert_eq!(smoothed.full_production, 1969);
+    assert!(matches!(smoothed.supply_state, SupplyState::Surplus(1469)));
+
+    let mut disabled = PowerSmoother::new(1.0, 10, 95);
+    disabled.apply(&reading(0, 500));
+    assert_eq!(disabled.apply(&reading(2000, -1500)).full_production, 2000);
+}
@@ -0,0 +1,136 @@
+use crate::calculator::{DataHistory, EnergyUnit, MqttPayload, ProcessedData};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::debug;
+
+/// Last successfully collected reading, kept on disk so a restart has
+/// something to publish if the inverter is not reachable right away.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub collected_at: DateTime<Utc>,
+    pub power_data: ProcessedData,
+    pub energy_data: DataHistory,
+}
+
+impl Snapshot {
+    pub fn new(power_data: &ProcessedData, energy_data: &DataHistory) -> Self {
+        Self {
+            collected_at: Utc::now(),
+            power_data: power_data.clone(),
+            energy_data: energy_data.clone(),
+        }
+    }
+
+    /// Writes to a temporary file first so a crash mid-write never leaves a
+    /// truncated snapshot behind.
+    pub async fn save(&self, path: &str) -> Result<()> {
+        if let Some(parent) = Path::new(path).parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .wrap_err_with(|| format!("Failed to create snapshot directory for {}", path))?;
+        }
+
+        let tmp_path = format!("{}.tmp", path);
+        let content = serde_json::to_vec_pretty(self)?;
+
+        tokio::fs::write(&tmp_path, content)
+            .await
+            .wrap_err_with(|| format!("Failed to write snapshot to {}", tmp_path))?;
+        tokio::fs::rename(&tmp_path, path)
+            .await
+            .wrap_err_with(|| format!("Failed to move snapshot to {}", path))?;
+
+        debug!(path = %path, "Snapshot saved");
+        Ok(())
+    }
+
+    /// Returns `None` if no snapshot has been written yet.
+    pub async fn load(path: &str) -> Result<Option<Self>> {
+        let content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).wrap_err_with(|| format!("Failed to read snapshot from {}", path));
+            }
+        };
+
+        let snapshot = serde_json::from_slice(&content)
+            .wrap_err_with(|| format!("Failed to parse snapshot from {}", path))?;
+
+        debug!(path = %path, "Snapshot loaded");
+        Ok(Some(snapshot))
+    }
+
+    pub fn stale_power_json(&self) -> serde_json::Value {
+        self.mark_stale(self.power_data.to_state_json())
+    }
+
+    pub fn stale_energy_json(&self, unit: EnergyUnit) -> serde_json::Value {
+        self.mark_stale(self.energy_data.to_state_json_with_unit(unit))
+    }
+
+    fn mark_stale(&self, mut payload: serde_json::Value) -> serde_json::Value {
+        payload["stale"] = serde_json::Value::Bool(true);
+        payload["collected_at"] = serde_json::Value::String(self.collected_at.to_rfc3339());
+        payload
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_round_trip() {
+    use crate::calculator::{BatteryState, BatteryStatus, SensorValue, SupplyState};
+
+    let path = "data/test_snapshot.json";
+    let _ = tokio::fs::remove_file(path).await;
+
+    assert!(Snapshot::load(path).await.unwrap().is_none());
+
+    let power_data = ProcessedData {
+        supply_state: SupplyState::Surplus(800),
+        battery_status: BatteryStatus {
+            battery_state: BatteryState::Loading(1200),
+            battery_percent: 64,
+            battery_energy: 6400.0,
+            battery_temperature: None,
+        },
+        full_production: 3500,
+        consumption: 1500,
+        ..Default::default()
+    };
+    let energy_data = DataHistory {
+        grid_buy: 1000,
+        grid_sell: 2000,
+        production_energy: 10000,
+        consumption_energy: 5000,
+        battery_loaded: 3000,
+        battery_discharge: 1000,
+        battery_cycles: 12,
+        self_consumed_energy: 8000,
+        counter_reset: false,
+    };
+
+    let snapshot = Snapshot::new(&power_data, &energy_data);
+    snapshot.save(path).await.unwrap();
+
+    let restored = Snapshot::load(path).await.unwrap().unwrap();
+    assert_eq!(restored.collected_at, snapshot.collected_at);
+    assert_eq!(
+        restored.power_data.to_state_json()["pv_production"],
+        power_data.to_state_json()["pv_production"]
+    );
+    assert_eq!(
+        restored
+            .power_data
+            .battery_status
+            .battery_state
+            .state_string(),
+        "charging"
+    );
+    assert_eq!(restored.energy_data.battery_cycles, 12);
+
+    let stale = restored.stale_power_json();
+    assert_eq!(stale["stale"], true);
+    assert_eq!(stale["battery_percent"], 64);
+}
@@ -0,0 +1,92 @@
+use crate::collector::RawPVData;
+
+/// Flags a frozen inverter feed that keeps serving the same reading.
+///
+/// Power readings alone can legitimately sit still (zero production at
+/// night), so a reading only counts as unchanged when the energy counters
+/// did not move either. As long as the house draws anything the consumption
+/// and grid counters keep advancing, a feed that repeats them is frozen.
+/// `min_cycles = 0` never flags the feed.
+#[derive(Debug, Clone)]
+pub struct StaleDetector {
+    min_cycles: u32,
+    previous: Option<RawPVData>,
+    unchanged_cycles: u32,
+}
+
+impl StaleDetector {
+    pub fn new(min_cycles: u32) -> Self {
+        Self {
+            min_cycles,
+            previous: None,
+            unchanged_cycles: 0,
+        }
+    }
+
+    pub fn unchanged_cycles(&self) -> u32 {
+        self.unchanged_cycles
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.min_cycles > 0 && self.unchanged_cycles >= self.min_cycles
+    }
+
+    /// Compares the reading to the previous one, returns whether the feed
+    /// just became stale so the caller warns once instead of every cycle.
+    pub fn observe(&mut self, raw_data: &RawPVData) -> bool {
+        let was_stale = self.is_stale();
+        let unchanged = self.previous.as_ref().is_some_and(|previous| {
+            previous.energy_data == raw_data.energy_data
+                && previous.power_data == raw_data.power_data
+        });
+
+        if unchanged {
+            self.unchanged_cycles = self.unchanged_cycles.saturating_add(1);
+        } else {
+            self.unchanged_cycles = 0;
+            self.previous = Some(raw_data.clone());
+        }
+
+        !was_stale && self.is_stale()
+    }
+}
+
+#[test]
+fn test_stale_after_identical_readings() {
+    let mut reading = RawPVData::default();
+    reading.energy_data.consumption_energy = 10_000;
+    reading.power_data.consumption_power = 250;
+
+    let mut detector = StaleDetector::new(3);
+    assert!(!detector.observe(&reading));
+    assert!(!detector.observe(&reading));
+    assert!(!detector.observe(&reading));
+    assert!(!detector.is_stale());
+
+    // Third repeat of the same reading trips the flag, only once
+    assert!(detector.observe(&reading));
+    assert!(detector.is_stale());
+    assert!(!detector.observe(&reading));
+    assert!(detector.is_stale());
+
+    // Moving counter clears it again
+    reading.energy_data.consumption_energy += 1;
+    assert!(!detector.observe(&reading));
+    assert!(!detector.is_stale());
+    assert_eq!(detector.unchanged_cycles(), 0);
+
+    // Night: no production and identical power, but the counters advance
+    let mut night = StaleDetector::new(3);
+    for cycle in 0..10 {
+        reading.energy_data.consumption_energy += 1;
+        reading.energy_data.grid_buy = cycle;
+        assert!(!night.observe(&reading));
+    }
+    assert!(!night.is_stale());
+
+    let mut disabled = StaleDetector::new(0);
+    for _ in 0..10 {
+        assert!(!disabled.observe(&reading));
+    }
+    assert!(!disabled.is_stale());
+}
@@ -0,0 +1,138 @@
+use crate::config::StateTimeConfig;
+use crate::metrics::Metrics;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Result, WrapErr};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::{debug, warn};
+
+/// Accumulates the time spent in each health state. The totals are written
+/// to disk so the `seconds_in_state` counters survive a restart. Time while
+/// the service is not running is not attributed to any state.
+#[derive(Debug, Clone)]
+pub struct StateTimeTracker {
+    config: StateTimeConfig,
+    totals: BTreeMap<String, f64>,
+    current: Option<(String, DateTime<Utc>)>,
+}
+
+impl StateTimeTracker {
+    pub fn new(config: StateTimeConfig) -> Self {
+        Self {
+            config,
+            totals: BTreeMap::new(),
+            current: None,
+        }
+    }
+
+    /// Restores the persisted totals and seeds the metrics with them. A
+    /// missing or unreadable file starts from zero.
+    pub async fn load(config: StateTimeConfig, metrics: &Metrics) -> Self {
+        let mut tracker = Self::new(config);
+        if !tracker.is_enabled() {
+            return tracker;
+        }
+
+        match tokio::fs::read(&tracker.config.path).await {
+            Ok(content) => match serde_json::from_slice::<BTreeMap<String, f64>>(&content) {
+                Ok(totals) => tracker.totals = totals,
+                Err(e) => {
+                    warn!(path = %tracker.config.path, error = %e, "Failed to parse state times")
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(path = %tracker.config.path, error = %e, "Failed to read state times"),
+        }
+
+        for (state, seconds) in &tracker.totals {
+            metrics.record_state_time(state, *seconds);
+        }
+        tracker
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Credits the time since the last update to the previous state and
+    /// continues counting for `state`. Called on every transition and after
+    /// every cycle.
+    pub fn update(&mut self, state: &str, now: DateTime<Utc>, metrics: &Metrics) {
+        if let Some((previous, since)) = self.current.take() {
+            let seconds = (now - since).num_milliseconds().max(0) as f64 / 1000.0;
+            *self.totals.entry(previous.clone()).or_default() += seconds;
+            metrics.record_state_time(&previous, seconds);
+        }
+        self.current = Some((state.to_string(), now));
+    }
+
+    pub fn seconds_in(&self, state: &str) -> f64 {
+        self.totals.get(state).copied().unwrap_or_default()
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let path = &self.config.path;
+        if let Some(parent) = Path::new(path).parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .wrap_err_with(|| format!("Failed to create state time directory for {}", path))?;
+        }
+
+        let tmp_path = format!("{}.tmp", path);
+        let content = serde_json::to_vec_pretty(&self.totals)?;
+
+        tokio::fs::write(&tmp_path, content)
+            .await
+            .wrap_err_with(|| format!("Failed to write state times to {}", tmp_path))?;
+        tokio::fs::rename(&tmp_path, path)
+            .await
+            .wrap_err_with(|| format!("Failed to move state times to {}", path))?;
+
+        debug!(path = %path, "State times saved");
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_seconds_in_state() {
+    let config = StateTimeConfig {
+        enabled: true,
+        path: "data/test_state_time.json".to_string(),
+    };
+    let _ = tokio::fs::remove_file(&config.path).await;
+
+    let metrics = Metrics::new();
+    let mut tracker = StateTimeTracker::load(config.clone(), &metrics).await;
+    let start = Utc::now();
+    let at = |secs: i64| start + chrono::Duration::seconds(secs);
+
+    tracker.update("Healthy", at(0), &metrics);
+    tracker.update("Healthy", at(60), &metrics);
+    tracker.update("DegradedNoDB", at(90), &metrics);
+    tracker.update("DegradedNoDB", at(120), &metrics);
+    tracker.update("Healthy", at(135), &metrics);
+    tracker.update("Healthy", at(150), &metrics);
+
+    assert_eq!(tracker.seconds_in("Healthy"), 105.0);
+    assert_eq!(tracker.seconds_in("DegradedNoDB"), 45.0);
+    assert_eq!(tracker.seconds_in("CacheOnly"), 0.0);
+
+    let rendered = metrics.render();
+    assert!(rendered.contains("seconds_in_state{state=\"Healthy\"} 105"));
+    assert!(rendered.contains("seconds_in_state{state=\"DegradedNoDB\"} 45"));
+
+    // Restart: totals come back from disk, the downtime is not counted
+    tracker.save().await.unwrap();
+    let restarted_metrics = Metrics::new();
+    let mut restarted = StateTimeTracker::load(config, &restarted_metrics).await;
+    restarted.update("Healthy", at(3600), &restarted_metrics);
+    restarted.update("Healthy", at(3610), &restarted_metrics);
+
+    assert_eq!(restarted.seconds_in("Healthy"), 115.0);
+    assert_eq!(restarted.seconds_in("DegradedNoDB"), 45.0);
+    assert!(
+        restarted_metrics
+            .render()
+            .contains("seconds_in_state{state=\"Healthy\"} 115")
+    );
+}
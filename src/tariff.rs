@@ -0,0 +1,271 @@
+use crate::calculator::DataHistory;
+use crate::config::TariffConfig;
+use crate::efficiency::EnergyDelta;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeDelta};
+use color_eyre::eyre::{Result, eyre};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Time-of-use window in local time, e.g. `peak=07:00-22:00`. A window whose
+/// end lies before its start wraps past midnight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TariffWindow {
+    pub name: String,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TariffWindow {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (name, range) = spec
+            .split_once('=')
+            .ok_or_else(|| eyre!("Tariff window '{}' must look like name=HH:MM-HH:MM", spec))?;
+        let (start, end) = range
+            .split_once('-')
+            .ok_or_else(|| eyre!("Tariff window '{}' must look like name=HH:MM-HH:MM", spec))?;
+
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|e| eyre!("Invalid time '{}' in tariff window '{}': {}", time, spec, e))
+        };
+
+        let window = Self {
+            name: name.trim().to_string(),
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        };
+
+        if window.name.is_empty() {
+            return Err(eyre!("Tariff window '{}' has no name", spec));
+        }
+        if window.start == window.end {
+            return Err(eyre!("Tariff window '{}' is empty", spec));
+        }
+        Ok(window)
+    }
+
+    /// Comma separated list of windows, an empty string gives no windows.
+    pub fn parse_list(spec: &str) -> Result<Vec<Self>> {
+        spec.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Grid energy attributed to one tariff window, in Wh.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TariffEnergy {
+    pub import_wh: f64,
+    pub export_wh: f64,
+}
+
+impl TariffEnergy {
+    fn add(&mut self, other: TariffEnergy) {
+        self.import_wh += other.import_wh;
+        self.export_wh += other.export_wh;
+    }
+}
+
+/// Splits grid import/export between two energy readings across the tariff
+/// windows the interval touched, proportional to the time spent in each.
+/// Time not covered by a configured window goes to `default_window`.
+#[derive(Debug, Clone)]
+pub struct TariffTracker {
+    config: TariffConfig,
+    windows: Vec<TariffWindow>,
+    last: Option<(DateTime<Local>, DataHistory)>,
+    daily: BTreeMap<(NaiveDate, String), TariffEnergy>,
+    pending: BTreeMap<(NaiveDate, String), TariffEnergy>,
+}
+
+impl TariffTracker {
+    pub fn new(config: TariffConfig) -> Result<Self> {
+        let windows = TariffWindow::parse_list(&config.windows)?;
+
+        Ok(Self {
+            config,
+            windows,
+            last: None,
+            daily: BTreeMap::new(),
+            pending: BTreeMap::new(),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Every window name, the default window last.
+    pub fn window_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.windows.iter().map(|w| w.name.clone()).collect();
+        if !names.contains(&self.config.default_window) {
+            names.push(self.config.default_window.clone());
+        }
+        names
+    }
+
+    pub fn push(&mut self, timestamp: DateTime<Local>, history: &DataHistory) {
+        let Some((since, previous)) = self.last.replace((timestamp, history.clone())) else {
+            return;
+        };
+
+        let delta = EnergyDelta::between(&previous, history);
+        let total_ms = (timestamp - since).num_milliseconds();
+        if total_ms <= 0 || (delta.grid_buy == 0 && delta.grid_sell == 0) {
+            return;
+        }
+
+        let today = timestamp.date_naive();
+        self.daily.retain(|(day, _), _| *day == today);
+
+        for (day, window, duration) in self.segments(since, timestamp) {
+            let share = duration.num_milliseconds() as f64 / total_ms as f64;
+            let energy = TariffEnergy {
+                import_wh: delta.grid_buy as f64 * share,
+                export_wh: delta.grid_sell as f64 * share,
+            };
+
+            self.pending
+                .entry((day, window.clone()))
+                .or_default()
+                .add(energy);
+            if day == today {
+                self.daily.entry((day, window)).or_default().add(energy);
+            }
+        }
+    }
+
+    pub fn today(&self, window: &str) -> TariffEnergy {
+        self.daily
+            .iter()
+            .find(|((_, name), _)| name == window)
+            .map(|(_, energy)| *energy)
+            .unwrap_or_default()
+    }
+
+    /// Increments not yet written to the database.
+    pub fn pending(&self) -> &BTreeMap<(NaiveDate, String), TariffEnergy> {
+        &self.pending
+    }
+
+    pub fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    pub fn payload(&self) -> serde_json::Value {
+        let mut payload = json!({
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        });
+        // Published in kWh like the other energy sensors
+        for window in self.window_names() {
+            let energy = self.today(&window);
+            payload[format!("{}_import", window)] = json!(energy.import_wh.round() / 1000.0);
+            payload[format!("{}_export", window)] = json!(energy.export_wh.round() / 1000.0);
+        }
+        payload
+    }
+
+    fn window_at(&self, time: NaiveTime) -> &str {
+        self.windows
+            .iter()
+            .find(|w| w.contains(time))
+            .map(|w| w.name.as_str())
+            .unwrap_or(&self.config.default_window)
+    }
+
+    /// Cuts `[from, to)` at every window boundary and at midnight.
+    fn segments(
+        &self,
+        from: DateTime<Local>,
+        to: DateTime<Local>,
+    ) -> Vec<(NaiveDate, String, TimeDelta)> {
+        let mut boundaries: Vec<NaiveTime> =
+            self.windows.iter().flat_map(|w| [w.start, w.end]).collect();
+        boundaries.sort();
+        boundaries.dedup();
+
+        let mut segments = Vec::new();
+        let mut cursor = from;
+        while cursor < to {
+            let time = cursor.time();
+            let next_boundary = match boundaries.iter().find(|b| **b > time) {
+                Some(boundary) => cursor.date_naive().and_time(*boundary),
+                None => (cursor.date_naive() + TimeDelta::days(1)).and_time(NaiveTime::MIN),
+            };
+            let segment_end = next_boundary
+                .and_local_timezone(Local)
+                .earliest()
+                .map_or(to, |boundary| boundary.min(to));
+            if segment_end <= cursor {
+                break;
+            }
+
+            segments.push((
+                cursor.date_naive(),
+                self.window_at(time).to_string(),
+                segment_end - cursor,
+            ));
+            cursor = segment_end;
+        }
+        segments
+    }
+}
+
+#[test]
+fn test_tariff_window_boundary_crossing() {
+    use chrono::TimeZone;
+
+    let mut tracker = TariffTracker::new(TariffConfig {
+        enabled: true,
+        windows: "peak=07:00-22:00".to_string(),
+        default_window: "off_peak".to_string(),
+    })
+    .unwrap();
+    let history = |grid_buy: u64, grid_sell: u64| DataHistory {
+        grid_buy,
+        grid_sell,
+        production_energy: 10_000,
+        consumption_energy: 5_000,
+        battery_loaded: 3_000,
+        battery_discharge: 1_000,
+        battery_cycles: 0,
+        self_consumed_energy: 8_000,
+        counter_reset: false,
+    };
+    let at = |hour: u32, minute: u32| {
+        Local
+            .with_ymd_and_hms(2025, 1, 15, hour, minute, 0)
+            .unwrap()
+    };
+
+    tracker.push(at(21, 50), &history(1_000, 500));
+    // 10 minutes peak, 10 minutes off-peak
+    tracker.push(at(22, 10), &history(1_400, 700));
+    // Entirely off-peak
+    tracker.push(at(22, 40), &history(1_700, 700));
+
+    let peak = tracker.today("peak");
+    let off_peak = tracker.today("off_peak");
+    assert!((peak.import_wh - 200.0).abs() < 0.01);
+    assert!((peak.export_wh - 100.0).abs() < 0.01);
+    assert!((off_peak.import_wh - 500.0).abs() < 0.01);
+    assert!((off_peak.export_wh - 100.0).abs() < 0.01);
+
+    let day = at(0, 0).date_naive();
+    assert_eq!(tracker.pending().len(), 2);
+    assert!(tracker.pending()[&(day, "peak".to_string())].import_wh > 199.99);
+
+    let payload = tracker.payload();
+    assert_eq!(payload["peak_import"], 0.2);
+    assert_eq!(payload["off_peak_import"], 0.5);
+}
@@ -0,0 +1,233 @@
+use crate::calculator::SensorValue;
+use crate::config::Config;
+use crate::health::{CoordinatorKind, run_until_shutdown};
+use crate::server::{CoordinatorStatus, SharedStatus};
+use chrono_tz::Tz;
+use color_eyre::eyre::{Result, eyre};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::info;
+
+const INPUT_POLL: Duration = Duration::from_millis(250);
+
+/// Runs the coordinator loop with a live terminal dashboard instead of the
+/// HTTP status server. `q` stops both and runs the normal shutdown cleanup.
+/// With several inverters only the first one is shown and collected.
+pub async fn run_tui() -> Result<()> {
+    info!("Starting coordinator with terminal dashboard");
+
+    let config = Config::new()?;
+    config.validate()?;
+    let timezone = config.tz();
+    let first = config.per_inverter().swap_remove(0);
+    let coordinator = CoordinatorKind::start_with(first).await?;
+    let status = SharedStatus::default();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+    let loop_status = status.clone();
+    let coordinator_task = tokio::spawn(async move {
+        let shutdown = async {
+            let _ = shutdown_rx.await;
+        };
+        run_until_shutdown(coordinator, loop_status, shutdown).await
+    });
+
+    let mut terminal = ratatui::init();
+    let result = draw_until_quit(&mut terminal, &status, timezone).await;
+    ratatui::restore();
+
+    let _ = shutdown_tx.send(());
+    coordinator_task
+        .await
+        .map_err(|e| eyre!("Coordinator task failed: {}", e))??;
+    result
+}
+
+async fn draw_until_quit(
+    terminal: &mut DefaultTerminal,
+    status: &SharedStatus,
+    timezone: Tz,
+) -> Result<()> {
+    loop {
+        let current = status.lock().await.clone();
+        terminal.draw(|frame| render(frame, &current, timezone))?;
+
+        // Blocks the worker thread while waiting for input
+        let quit = tokio::task::block_in_place(|| -> Result<bool> {
+            if !event::poll(INPUT_POLL)? {
+                return Ok(false);
+            }
+            Ok(match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    key.code == KeyCode::Char('q')
+                        || (key.code == KeyCode::Char('c')
+                            && key.modifiers.contains(KeyModifiers::CONTROL))
+                }
+                _ => false,
+            })
+        })?;
+
+        if quit {
+            return Ok(());
+        }
+    }
+}
+
+/// Draws one frame from the coordinator status, times in `timezone`.
+pub fn render(frame: &mut Frame, status: &CoordinatorStatus, timezone: Tz) {
+    let [health_area, power_area, battery_area, history_area] = Layout::vertical([
+        Constraint::Length(4),
+        Constraint::Length(7),
+        Constraint::Length(3),
+        Constraint::Min(3),
+    ])
+    .areas(frame.area());
+
+    let last_cycle = status
+        .last_successful_cycle
+        .map(|t| t.with_timezone(&timezone).format("%H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string());
+    let health = Paragraph::new(vec![
+        Line::from(vec![
+            "State: ".into(),
+            status.state.clone().bold().fg(state_color(&status.state)),
+            format!("   Last cycle: {}", last_cycle).into(),
+        ]),
+        Line::from(format!(
+            "Postgres: {}   MQTT: {}   Cache backlog: {} rows",
+            status.postgres, status.mqtt, status.cache_backlog
+        )),
+    ])
+    .block(Block::bordered().title(" PV API (q to quit) "));
+    frame.render_widget(health, health_area);
+
+    let latest = status.recent.back();
+    let power_lines = match latest {
+        Some(snapshot) => {
+            let data = &snapshot.power_data;
+            vec![
+                Line::from(format!("PV production: {:>6} W", data.full_production)),
+                Line::from(format!("Consumption:   {:>6} W", data.consumption)),
+                Line::from(format!(
+                    "Grid:          {:>6} W  ({})",
+                    data.supply_state.power_value(),
+                    data.supply_state.state_string()
+                )),
+                Line::from(format!(
+                    "Battery:       {:>6} W  ({})",
+                    data.battery_status.battery_state.power_value(),
+                    data.battery_status.battery_state.state_string()
+                )),
+                Line::from(format!(
+                    "Phases:        L1 {} W  L2 {} W  L3 {} W",
+                    data.phase_power.l1, data.phase_power.l2, data.phase_power.l3
+                )),
+            ]
+        }
+        None => vec![Line::from("Waiting for the first reading")],
+    };
+    frame.render_widget(
+        Paragraph::new(power_lines).block(Block::bordered().title(" Power flows ")),
+        power_area,
+    );
+
+    let percent = latest.map_or(0, |s| s.power_data.battery_status.battery_percent.min(100));
+    frame.render_widget(
+        Gauge::default()
+            .block(Block::bordered().title(" Battery SOC "))
+            .gauge_style(Style::default().fg(Color::Green))
+            .percent(percent as u16),
+        battery_area,
+    );
+
+    let production: Vec<u64> = status
+        .recent
+        .iter()
+        .map(|s| s.power_data.full_production as u64)
+        .collect();
+    frame.render_widget(
+        Sparkline::default()
+            .block(Block::bordered().title(" PV production history "))
+            .style(Style::default().fg(Color::Yellow))
+            .data(&production),
+        history_area,
+    );
+}
+
+fn state_color(state: &str) -> Color {
+    match state {
+        "Healthy" => Color::Green,
+        "Shutdown" => Color::Red,
+        "Starting" => Color::Gray,
+        _ => Color::Yellow,
+    }
+}
+
+#[test]
+fn test_render_sample_snapshot() {
+    use crate::calculator::{BatteryState, BatteryStatus, DataHistory, ProcessedData, SupplyState};
+    use crate::snapshot::Snapshot;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    let power_data = ProcessedData {
+        supply_state: SupplyState::Demand(350),
+        battery_status: BatteryStatus {
+            battery_state: BatteryState::Loading(1200),
+            battery_percent: 64,
+            battery_energy: 6400.0,
+            battery_temperature: None,
+        },
+        full_production: 4100,
+        consumption: 3250,
+        ..Default::default()
+    };
+    let energy_data = DataHistory {
+        grid_buy: 1_000,
+        grid_sell: 2_000,
+        production_energy: 10_000,
+        consumption_energy: 5_000,
+        battery_loaded: 3_000,
+        battery_discharge: 1_000,
+        battery_cycles: 0,
+        self_consumed_energy: 8_000,
+        counter_reset: false,
+    };
+
+    let mut status = CoordinatorStatus {
+        state: "Healthy".to_string(),
+        cache_backlog: 12,
+        last_successful_cycle: Some("2026-07-01T10:00:00Z".parse().unwrap()),
+        ..Default::default()
+    };
+    status.push_reading(&Snapshot::new(&power_data, &energy_data));
+
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    terminal
+        .draw(|frame| render(frame, &status, Tz::Europe__Berlin))
+        .unwrap();
+
+    let content: String = terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|cell| cell.symbol())
+        .collect();
+    assert!(content.contains("Healthy"));
+    assert!(content.contains("4100 W"));
+    assert!(content.contains("Cache backlog: 12 rows"));
+    assert!(content.contains("64%"));
+    assert!(content.contains("Last cycle: 12:00:00"));
+
+    // Without any reading the frame still renders
+    terminal
+        .draw(|frame| render(frame, &CoordinatorStatus::default(), Tz::UTC))
+        .unwrap();
+}